@@ -0,0 +1,6 @@
+CREATE TABLE game_server_votes (
+    guild_id      INTEGER NOT NULL,
+    voter_id      INTEGER NOT NULL,
+    target_id     INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, voter_id)
+);
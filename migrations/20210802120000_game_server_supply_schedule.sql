@@ -0,0 +1,4 @@
+ALTER TABLE game_servers ADD COLUMN supply_channel_id INTEGER;
+ALTER TABLE game_servers ADD COLUMN supply_interval INTEGER;
+ALTER TABLE game_servers ADD COLUMN supply_actions INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE game_servers ADD COLUMN supply_next INTEGER;
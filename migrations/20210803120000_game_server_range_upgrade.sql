@@ -0,0 +1,2 @@
+ALTER TABLE game_servers ADD COLUMN upgrade_cost INTEGER NOT NULL DEFAULT 3;
+ALTER TABLE game_servers ADD COLUMN max_range INTEGER NOT NULL DEFAULT 5;
@@ -0,0 +1,3 @@
+ALTER TABLE game_servers ADD COLUMN status TEXT NOT NULL DEFAULT 'lobby';
+-- Games that already existed were already being played
+UPDATE game_servers SET status = 'running';
@@ -0,0 +1,2 @@
+ALTER TABLE game_servers ADD COLUMN winner_id INTEGER;
+ALTER TABLE game_servers ADD COLUMN finished_at INTEGER;
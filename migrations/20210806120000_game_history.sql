@@ -0,0 +1,25 @@
+ALTER TABLE game_servers ADD COLUMN created_at INTEGER;
+
+CREATE TABLE game_history (
+    id             INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    guild_id       INTEGER NOT NULL,
+    name           TEXT    NOT NULL,
+    width          INTEGER NOT NULL,
+    height         INTEGER NOT NULL,
+    status         TEXT    NOT NULL,
+    winner_id      INTEGER,
+    created_at     INTEGER,
+    finished_at    INTEGER NOT NULL
+);
+
+CREATE INDEX game_history_guild ON game_history (guild_id);
+
+CREATE TABLE game_history_players (
+    game_id       INTEGER NOT NULL,
+    user_id       INTEGER NOT NULL,
+    placement     INTEGER NOT NULL,
+    health        INTEGER NOT NULL,
+    actions       INTEGER NOT NULL,
+    range         INTEGER NOT NULL,
+    PRIMARY KEY (game_id, user_id)
+);
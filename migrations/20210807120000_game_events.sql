@@ -0,0 +1,15 @@
+CREATE TABLE game_events (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    guild_id      INTEGER NOT NULL,
+    -- Set once the game is archived, NULL while the game is still current
+    history_id    INTEGER,
+    -- NULL when done by the bot itself, such as a scheduled supply
+    actor_id      INTEGER,
+    target_id     INTEGER,
+    kind          TEXT    NOT NULL,
+    before        TEXT,
+    after         TEXT,
+    created_at    INTEGER NOT NULL
+);
+
+CREATE INDEX game_events_guild ON game_events (guild_id, history_id);
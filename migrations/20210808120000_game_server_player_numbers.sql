@@ -0,0 +1,17 @@
+ALTER TABLE game_server_players ADD COLUMN number INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE game_server_players ADD COLUMN color TEXT NOT NULL DEFAULT 'red';
+
+-- Number existing players the same way the board already did, in user id order
+UPDATE game_server_players SET number = (
+    SELECT COUNT(*) FROM game_server_players p
+    WHERE p.guild_id = game_server_players.guild_id AND p.user_id < game_server_players.user_id
+);
+UPDATE game_server_players SET color = CASE number % 7
+    WHEN 0 THEN 'red'
+    WHEN 1 THEN 'orange'
+    WHEN 2 THEN 'yellow'
+    WHEN 3 THEN 'green'
+    WHEN 4 THEN 'blue'
+    WHEN 5 THEN 'purple'
+    ELSE 'brown'
+END;
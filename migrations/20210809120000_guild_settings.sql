@@ -0,0 +1,4 @@
+CREATE TABLE guild_settings (
+    guild_id       INTEGER PRIMARY KEY NOT NULL,
+    theme          TEXT    NOT NULL DEFAULT 'light'
+);
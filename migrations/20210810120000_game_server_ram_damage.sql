@@ -0,0 +1 @@
+ALTER TABLE game_servers ADD COLUMN ram_damage INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+CREATE TABLE game_terrain (
+    guild_id       INTEGER NOT NULL,
+    history_id     INTEGER,
+    pos_x          INTEGER NOT NULL,
+    pos_y          INTEGER NOT NULL,
+    kind           TEXT    NOT NULL
+);
+
+CREATE INDEX game_terrain_game ON game_terrain (guild_id, history_id);
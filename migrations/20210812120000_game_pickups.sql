@@ -0,0 +1,9 @@
+ALTER TABLE game_servers ADD COLUMN pickup_spawns INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE game_pickups (
+    guild_id       INTEGER NOT NULL,
+    pos_x          INTEGER NOT NULL,
+    pos_y          INTEGER NOT NULL,
+    kind           TEXT    NOT NULL,
+    PRIMARY KEY (guild_id, pos_x, pos_y)
+);
@@ -0,0 +1,2 @@
+-- Left empty for games archived before colors were kept, replays fall back to the join order colors
+ALTER TABLE game_history_players ADD COLUMN color TEXT;
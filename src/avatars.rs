@@ -0,0 +1,80 @@
+use image::imageops::FilterType;
+use image::RgbaImage;
+use serenity::model::id::UserId;
+use serenity::model::user::User;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Avatars are stored at this size, which is plenty for the largest tiles
+const AVATAR_SIZE: u32 = 64;
+
+fn cache_dir() -> PathBuf {
+	std::env::var("AVATAR_CACHE_DIR")
+		.unwrap_or_else(|_| "avatar-cache".to_string())
+		.into()
+}
+
+/// Loads the avatars of the given users, fetching them from discord only the first time and then
+/// keeping them on disk.  Users whose avatar could not be loaded are left out.
+pub async fn load_avatars(users: &[User]) -> HashMap<UserId, RgbaImage> {
+	let dir = cache_dir();
+	if let Err(reason) = tokio::fs::create_dir_all(&dir).await {
+		eprintln!("unable to create avatar cache {:?}: {:?}", dir, reason);
+	}
+	let client = reqwest::Client::new();
+	let mut avatars = HashMap::with_capacity(users.len());
+	for user in users {
+		match load_avatar(&client, &dir, user).await {
+			Ok(avatar) => {
+				avatars.insert(user.id, avatar);
+			}
+			Err(reason) => eprintln!("unable to load avatar of {}: {:?}", user.id, reason),
+		}
+	}
+	avatars
+}
+
+async fn load_avatar(
+	client: &reqwest::Client,
+	dir: &std::path::Path,
+	user: &User,
+) -> anyhow::Result<RgbaImage> {
+	// The avatar hash changes whenever they change their avatar so old entries are never reused
+	let path = dir.join(format!(
+		"{}-{}.png",
+		user.id,
+		user.avatar.as_deref().unwrap_or("default")
+	));
+	if let Ok(data) = tokio::fs::read(&path).await {
+		return Ok(image::load_from_memory(&data)?.into_rgba8());
+	}
+
+	let url = match &user.avatar {
+		Some(hash) => format!(
+			"https://cdn.discordapp.com/avatars/{}/{}.png?size={}",
+			user.id, hash, AVATAR_SIZE
+		),
+		None => format!(
+			"https://cdn.discordapp.com/embed/avatars/{}.png",
+			user.discriminator % 5
+		),
+	};
+	let data = client
+		.get(&url)
+		.send()
+		.await?
+		.error_for_status()?
+		.bytes()
+		.await?;
+	let avatar = image::load_from_memory(&data)?.into_rgba8();
+	let avatar = image::imageops::resize(&avatar, AVATAR_SIZE, AVATAR_SIZE, FilterType::Triangle);
+	let mut encoded = Vec::new();
+	image::png::PngEncoder::new(&mut encoded).encode(
+		avatar.as_raw(),
+		AVATAR_SIZE,
+		AVATAR_SIZE,
+		image::ColorType::Rgba8,
+	)?;
+	tokio::fs::write(&path, encoded).await?;
+	Ok(avatar)
+}
@@ -1,13 +1,17 @@
 use sqlx::{Sqlite, SqlitePool, Transaction};
 
+use crate::helpers::random_empty_tile;
+use crate::terrain::{Terrain, TerrainMap};
 use anyhow::Context as AnyHowContext;
 use serenity::client::Context;
 use serenity::model::channel::Message;
 use serenity::model::id::{GuildId, UserId};
 use serenity::prelude::TypeMapKey;
 use sqlx::pool::PoolConnection;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
-pub struct DB(SqlitePool);
+pub struct DB;
 impl TypeMapKey for DB {
 	type Value = SqlitePool;
 }
@@ -31,18 +35,290 @@ impl DB {
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameStatus {
+	Lobby,
+	Running,
+	Paused,
+	Finished,
+}
+
+impl FromStr for GameStatus {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"lobby" => GameStatus::Lobby,
+			"running" => GameStatus::Running,
+			"paused" => GameStatus::Paused,
+			"finished" => GameStatus::Finished,
+			_ => anyhow::bail!("invalid game status: {}", s),
+		})
+	}
+}
+
+impl GameStatus {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			GameStatus::Lobby => "lobby",
+			GameStatus::Running => "running",
+			GameStatus::Paused => "paused",
+			GameStatus::Finished => "finished",
+		}
+	}
+
+	pub fn describe(self) -> &'static str {
+		match self {
+			GameStatus::Lobby => "waiting in the lobby",
+			GameStatus::Running => "running",
+			GameStatus::Paused => "paused",
+			GameStatus::Finished => "finished",
+		}
+	}
+
+	/// Fails unless the status is one of `allowed`, replying to the message with why if given
+	pub async fn require(
+		self,
+		allowed: &[GameStatus],
+		doing: &str,
+		ctx_msg: Option<(&Context, &Message)>,
+	) -> anyhow::Result<()> {
+		if allowed.contains(&self) {
+			return Ok(());
+		}
+		if let Some((ctx, msg)) = ctx_msg {
+			msg.reply(
+				ctx,
+				format!("Cannot {} while the game is {}", doing, self.describe()),
+			)
+			.await?;
+		}
+		anyhow::bail!("cannot {} while the game is {}", doing, self.as_str());
+	}
+}
+
+/// Board color scheme, set per server rather than per game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Theme {
+	#[default]
+	Light,
+	Dark,
+	HighContrast,
+	ColorblindSafe,
+}
+
+impl FromStr for Theme {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.trim().to_lowercase().replace('_', "-").as_str() {
+			"light" => Theme::Light,
+			"dark" => Theme::Dark,
+			"high-contrast" | "contrast" => Theme::HighContrast,
+			"colorblind-safe" | "colorblind" => Theme::ColorblindSafe,
+			_ => anyhow::bail!("invalid theme: {}", s),
+		})
+	}
+}
+
+impl Theme {
+	pub const ALL: [Theme; 4] = [
+		Theme::Light,
+		Theme::Dark,
+		Theme::HighContrast,
+		Theme::ColorblindSafe,
+	];
+
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Theme::Light => "light",
+			Theme::Dark => "dark",
+			Theme::HighContrast => "high-contrast",
+			Theme::ColorblindSafe => "colorblind-safe",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Hash)]
 pub struct GameServer {
 	pub guild_id: GuildId,
 	pub name: String,
 	pub width: u8,
 	pub height: u8,
+	pub upgrade_cost: u8,
+	pub max_range: u8,
+	/// Damage done by driving into another tank, which only blocks the way if this is 0
+	pub ram_damage: u8,
+	/// Pickups that appear each supply period
+	pub pickup_spawns: u8,
+	pub status: GameStatus,
+	pub terrain: TerrainMap,
+	pub pickups: Vec<Pickup>,
 }
 
-#[derive(Debug)]
+impl GameServer {
+	/// Whether a tank or pickup could be placed on the tile, nothing is there and it can be driven onto
+	pub fn is_empty_tile(&self, players: &[GamePlayer], x: u8, y: u8) -> bool {
+		self.terrain.get(x, y).is_passable()
+			&& !players.iter().any(|p| p.pos_x == x && p.pos_y == y)
+			&& !self.pickups.iter().any(|p| p.pos_x == x && p.pos_y == y)
+	}
+}
+
+/// Actions in an action crate
+pub const CRATE_ACTIONS: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PickupKind {
+	/// Gives a health back, up to the max
+	Heart,
+	/// Gives `CRATE_ACTIONS` actions
+	Crate,
+}
+
+impl FromStr for PickupKind {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"heart" => PickupKind::Heart,
+			"crate" => PickupKind::Crate,
+			_ => anyhow::bail!("invalid pickup kind: {}", s),
+		})
+	}
+}
+
+impl PickupKind {
+	pub const ALL: [PickupKind; 2] = [PickupKind::Heart, PickupKind::Crate];
+
+	pub fn as_str(self) -> &'static str {
+		match self {
+			PickupKind::Heart => "heart",
+			PickupKind::Crate => "crate",
+		}
+	}
+
+	pub fn describe(self) -> &'static str {
+		match self {
+			PickupKind::Heart => "a heart",
+			PickupKind::Crate => "an action crate",
+		}
+	}
+
+	/// Used by the text board
+	pub fn symbol(self) -> char {
+		match self {
+			PickupKind::Heart => '+',
+			PickupKind::Crate => '$',
+		}
+	}
+
+	pub fn apply(self, player: &mut GamePlayer) {
+		match self {
+			PickupKind::Heart => player.health = (player.health + 1).min(MAX_HEALTH),
+			PickupKind::Crate => player.actions = player.actions.saturating_add(CRATE_ACTIONS),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pickup {
+	pub pos_x: u8,
+	pub pos_y: u8,
+	pub kind: PickupKind,
+}
+
+pub const MAX_HEALTH: u8 = 3;
+
+/// Limited to the colors discord has square emoji for so the embed can show them too
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlayerColor {
+	Red,
+	Orange,
+	Yellow,
+	Green,
+	Blue,
+	Purple,
+	Brown,
+}
+
+impl FromStr for PlayerColor {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.trim().to_lowercase().as_str() {
+			"red" => PlayerColor::Red,
+			"orange" => PlayerColor::Orange,
+			"yellow" => PlayerColor::Yellow,
+			"green" => PlayerColor::Green,
+			"blue" => PlayerColor::Blue,
+			"purple" => PlayerColor::Purple,
+			"brown" => PlayerColor::Brown,
+			_ => anyhow::bail!("invalid player color: {}", s),
+		})
+	}
+}
+
+impl PlayerColor {
+	pub const ALL: [PlayerColor; 7] = [
+		PlayerColor::Red,
+		PlayerColor::Orange,
+		PlayerColor::Yellow,
+		PlayerColor::Green,
+		PlayerColor::Blue,
+		PlayerColor::Purple,
+		PlayerColor::Brown,
+	];
+
+	/// Handed out in join order so the first few players all differ
+	pub fn for_number(number: u16) -> Self {
+		Self::ALL[number as usize % Self::ALL.len()]
+	}
+
+	pub fn as_str(self) -> &'static str {
+		match self {
+			PlayerColor::Red => "red",
+			PlayerColor::Orange => "orange",
+			PlayerColor::Yellow => "yellow",
+			PlayerColor::Green => "green",
+			PlayerColor::Blue => "blue",
+			PlayerColor::Purple => "purple",
+			PlayerColor::Brown => "brown",
+		}
+	}
+
+	pub fn rgb(self) -> (u8, u8, u8) {
+		match self {
+			PlayerColor::Red => (221, 46, 68),
+			PlayerColor::Orange => (244, 144, 12),
+			PlayerColor::Yellow => (253, 203, 88),
+			PlayerColor::Green => (120, 177, 89),
+			PlayerColor::Blue => (85, 172, 238),
+			PlayerColor::Purple => (170, 142, 214),
+			PlayerColor::Brown => (193, 105, 79),
+		}
+	}
+
+	pub fn emoji(self) -> &'static str {
+		match self {
+			PlayerColor::Red => "🟥",
+			PlayerColor::Orange => "🟧",
+			PlayerColor::Yellow => "🟨",
+			PlayerColor::Green => "🟩",
+			PlayerColor::Blue => "🟦",
+			PlayerColor::Purple => "🟪",
+			PlayerColor::Brown => "🟫",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Hash)]
 pub struct GamePlayer {
 	pub guild_id: GuildId,
 	pub user_id: UserId,
+	/// Assigned on join and never reused in the game, unlike their position in the player list
+	pub number: u16,
+	pub color: PlayerColor,
 	pub pos_x: u8,
 	pub pos_y: u8,
 	pub health: u8,
@@ -50,20 +326,378 @@ pub struct GamePlayer {
 	pub range: u8,
 }
 
+impl GamePlayer {
+	/// Chebyshev distance, diagonals count the same as axial moves
+	pub fn distance_to(&self, other: &GamePlayer) -> u8 {
+		debug_assert_eq!(self.guild_id, other.guild_id);
+		let dx = (self.pos_x as i16 - other.pos_x as i16).abs();
+		let dy = (self.pos_y as i16 - other.pos_y as i16).abs();
+		dx.max(dy) as u8
+	}
+
+	/// Snapshot stored in the event log as `pos_x,pos_y,health,actions,range`
+	pub fn state(&self) -> String {
+		format!(
+			"{},{},{},{},{}",
+			self.pos_x, self.pos_y, self.health, self.actions, self.range
+		)
+	}
+}
+
+/// A player snapshot parsed back out of the event log, see [`GamePlayer::state`]
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerState {
+	pub pos_x: u8,
+	pub pos_y: u8,
+	pub health: u8,
+	pub actions: u8,
+	pub range: u8,
+}
+
+impl FromStr for PlayerState {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut values = s.split(',').map(|v| v.parse::<u8>());
+		let mut next = || -> anyhow::Result<u8> {
+			Ok(values.next().context("player state is missing values")??)
+		};
+		Ok(PlayerState {
+			pos_x: next()?,
+			pos_y: next()?,
+			health: next()?,
+			actions: next()?,
+			range: next()?,
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+	Join,
+	Move,
+	Attack,
+	Ram,
+	Give,
+	Supply,
+	Upgrade,
+	Vote,
+	Jury,
+	Status,
+	Pickup,
+}
+
+impl FromStr for EventKind {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"join" => EventKind::Join,
+			"move" => EventKind::Move,
+			"attack" => EventKind::Attack,
+			"ram" => EventKind::Ram,
+			"give" => EventKind::Give,
+			"supply" => EventKind::Supply,
+			"upgrade" => EventKind::Upgrade,
+			"vote" => EventKind::Vote,
+			"jury" => EventKind::Jury,
+			"status" => EventKind::Status,
+			"pickup" => EventKind::Pickup,
+			_ => anyhow::bail!("invalid event kind: {}", s),
+		})
+	}
+}
+
+#[derive(Debug)]
+pub struct GameEvent {
+	pub id: i64,
+	pub actor_id: Option<UserId>,
+	pub target_id: Option<UserId>,
+	pub kind: EventKind,
+	pub before: Option<String>,
+	pub after: Option<String>,
+	pub created_at: i64,
+}
+
+impl GameEvent {
+	/// Replays this event onto `players`, returning whether anything shown on the board changed.
+	/// Players are drawn in their color from `colors`, or the default for their number if missing.
+	pub fn apply(
+		&self,
+		guild_id: GuildId,
+		players: &mut BTreeMap<UserId, GamePlayer>,
+		colors: &BTreeMap<UserId, PlayerColor>,
+	) -> bool {
+		let after = match self
+			.after
+			.as_deref()
+			.and_then(|s| s.parse::<PlayerState>().ok())
+		{
+			Some(after) => after,
+			None => return false,
+		};
+		let subject = match self.target_id.or(self.actor_id) {
+			Some(subject) => subject,
+			None => return false,
+		};
+		// Only the target's state is logged, so work out what it cost the actor
+		let before = self
+			.before
+			.as_deref()
+			.and_then(|s| s.parse::<PlayerState>().ok());
+		if let (Some(actor), Some(before), Some(_)) = (self.actor_id, before, self.target_id) {
+			if let Some(actor) = players.get_mut(&actor) {
+				match self.kind {
+					EventKind::Attack | EventKind::Ram => {
+						let looted = before.actions.saturating_sub(after.actions);
+						actor.actions = actor.actions.saturating_sub(1).saturating_add(looted);
+					}
+					EventKind::Give => {
+						actor.health = actor
+							.health
+							.saturating_sub(after.health.saturating_sub(before.health));
+						actor.actions = actor
+							.actions
+							.saturating_sub(after.actions.saturating_sub(before.actions));
+					}
+					_ => (),
+				}
+			}
+		}
+		// Numbers are handed out in join order so they come out the same as when it was played
+		let number = players
+			.get(&subject)
+			.map(|p| p.number)
+			.unwrap_or(players.len() as u16);
+		players.insert(
+			subject,
+			GamePlayer {
+				guild_id,
+				user_id: subject,
+				number,
+				color: colors
+					.get(&subject)
+					.copied()
+					.unwrap_or_else(|| PlayerColor::for_number(number)),
+				pos_x: after.pos_x,
+				pos_y: after.pos_y,
+				health: after.health,
+				actions: after.actions,
+				range: after.range,
+			},
+		);
+		true
+	}
+
+	/// A single line summary using mentions for the players involved
+	pub fn describe(&self) -> String {
+		let actor = self
+			.actor_id
+			.map(|u| format!("<@{}>", u))
+			.unwrap_or_else(|| "The bot".to_string());
+		let target = self
+			.target_id
+			.map(|u| format!("<@{}>", u))
+			.unwrap_or_default();
+		if self.kind == EventKind::Status {
+			let before = self.before.as_deref().unwrap_or("nothing");
+			let after = self.after.as_deref().unwrap_or("nothing");
+			return match self.actor_id {
+				Some(_) => format!("{} changed the game from {} to {}", actor, before, after),
+				None => format!(
+					"The game is {}, {} is the last tank standing",
+					after, target
+				),
+			};
+		}
+		let before = self
+			.before
+			.as_deref()
+			.and_then(|s| s.parse::<PlayerState>().ok());
+		let after = self
+			.after
+			.as_deref()
+			.and_then(|s| s.parse::<PlayerState>().ok());
+		match (self.kind, before, after) {
+			(EventKind::Join, _, Some(a)) => {
+				format!("{} joined at {},{}", actor, a.pos_x, a.pos_y)
+			}
+			(EventKind::Move, Some(b), Some(a)) => format!(
+				"{} moved from {},{} to {},{}",
+				actor, b.pos_x, b.pos_y, a.pos_x, a.pos_y
+			),
+			(EventKind::Attack, Some(_), Some(a)) if a.health == 0 => {
+				format!("{} destroyed {}", actor, target)
+			}
+			(EventKind::Attack, Some(b), Some(a)) => format!(
+				"{} hit {}, health {} to {}",
+				actor, target, b.health, a.health
+			),
+			(EventKind::Ram, Some(_), Some(a)) if a.health == 0 => {
+				format!("{} rammed and destroyed {}", actor, target)
+			}
+			(EventKind::Ram, Some(b), Some(a)) => format!(
+				"{} rammed {}, health {} to {}",
+				actor, target, b.health, a.health
+			),
+			(EventKind::Give, Some(b), Some(a)) if a.health != b.health => format!(
+				"{} gave {} {} health",
+				actor,
+				target,
+				a.health as i16 - b.health as i16
+			),
+			(EventKind::Give, Some(b), Some(a)) => format!(
+				"{} gave {} {} actions",
+				actor,
+				target,
+				a.actions as i16 - b.actions as i16
+			),
+			(EventKind::Supply, Some(b), Some(a)) => format!(
+				"{} supplied {} with {} actions",
+				actor,
+				target,
+				a.actions as i16 - b.actions as i16
+			),
+			(EventKind::Upgrade, _, Some(a)) => {
+				format!("{} upgraded to range {}", actor, a.range)
+			}
+			(EventKind::Vote, _, _) => format!("{} voted for {}", actor, target),
+			(EventKind::Jury, _, _) => format!("The jury gave {} an extra action", target),
+			(EventKind::Pickup, _, Some(a)) => format!(
+				"{} picked up {} at {},{}",
+				actor,
+				self.before
+					.as_deref()
+					.and_then(|s| s.parse::<PickupKind>().ok())
+					.map_or("something", |kind| kind.describe()),
+				a.pos_x,
+				a.pos_y
+			),
+			(kind, _, _) => format!("{} {} {}", actor, kind.as_str(), target),
+		}
+	}
+}
+
+impl EventKind {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			EventKind::Join => "join",
+			EventKind::Move => "move",
+			EventKind::Attack => "attack",
+			EventKind::Ram => "ram",
+			EventKind::Give => "give",
+			EventKind::Supply => "supply",
+			EventKind::Upgrade => "upgrade",
+			EventKind::Vote => "vote",
+			EventKind::Jury => "jury",
+			EventKind::Status => "status",
+			EventKind::Pickup => "pickup",
+		}
+	}
+}
+
 #[serenity::async_trait]
 pub trait DBGame {
+	/// The game's settings and status, its `terrain` and `pickups` are left empty
 	async fn get_game(
 		&mut self,
 		guild_id_: i64,
 		ctx_msg: Option<(&Context, &Message)>,
 	) -> anyhow::Result<GameServer>;
 
+	/// Like `get_game` but also loads what is on the board, for drawing it or moving around on it
+	async fn get_game_board(
+		&mut self,
+		guild_id_: i64,
+		ctx_msg: Option<(&Context, &Message)>,
+	) -> anyhow::Result<GameServer>;
+
 	async fn get_player(
 		&mut self,
 		guild_id_: i64,
 		user_id_: i64,
 		ctx_msg: Option<(&Context, &Message)>,
 	) -> anyhow::Result<GamePlayer>;
+
+	/// All players in the game ordered by their player number
+	async fn get_players(&mut self, guild_id_: i64) -> anyhow::Result<Vec<GamePlayer>>;
+
+	/// Appends to the event log of the current game.  `before` and `after` are the state of the
+	/// target, or of the actor when there is no target, `actor` is `None` for things the bot did.
+	async fn log_event(
+		&mut self,
+		guild_id_: i64,
+		actor: Option<UserId>,
+		target: Option<UserId>,
+		kind: EventKind,
+		before: Option<String>,
+		after: Option<String>,
+	) -> anyhow::Result<()>;
+
+	/// Most recent events first, of the current game or of an archived one if `history_id` is given,
+	/// optionally only those involving a player or of a single kind.  A negative limit is unlimited.
+	async fn get_events(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+		user_id_: Option<i64>,
+		kind: Option<EventKind>,
+		limit: i64,
+	) -> anyhow::Result<Vec<GameEvent>>;
+
+	/// Ends the current voting period, the living player with the most votes gets an extra action,
+	/// a tie gives nobody anything.  Returns the winner and their vote count.
+	async fn tally_votes(&mut self, guild_id_: i64) -> anyhow::Result<Option<(UserId, i64)>>;
+
+	/// Finishes a running game once only a single tank is left alive, returning that winner
+	async fn check_winner(&mut self, guild_id_: i64) -> anyhow::Result<Option<UserId>>;
+
+	/// An archived game as it was when it ended, the most recent one in the server if no id is given
+	async fn get_history_game(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+	) -> anyhow::Result<(i64, GameServer)>;
+
+	/// Copies the game and its final standings into the history tables, returning the history id.
+	/// `status` is recorded as how the game ended, `finished` or `destroyed`.
+	async fn archive_game(&mut self, guild_id_: i64, status: &str) -> anyhow::Result<i64>;
+
+	/// Colors the players picked in the current game, or in an archived one if `history_id` is given
+	async fn get_colors(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+	) -> anyhow::Result<BTreeMap<UserId, PlayerColor>>;
+
+	/// Terrain of the current game, or of an archived one if `history_id` is given
+	async fn get_terrain(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+	) -> anyhow::Result<TerrainMap>;
+
+	/// Stores the terrain of a newly created game
+	async fn set_terrain(&mut self, guild_id_: i64, terrain: &TerrainMap) -> anyhow::Result<()>;
+
+	async fn get_pickups(&mut self, guild_id_: i64) -> anyhow::Result<Vec<Pickup>>;
+
+	/// Removes the pickup on a tile, returning what it was
+	async fn take_pickup(
+		&mut self,
+		guild_id_: i64,
+		x: u8,
+		y: u8,
+	) -> anyhow::Result<Option<PickupKind>>;
+
+	/// Places the game's `pickup_spawns` worth of random pickups on random empty tiles, returning
+	/// the ones that fit
+	async fn spawn_pickups(&mut self, guild_id_: i64) -> anyhow::Result<Vec<Pickup>>;
+
+	/// The server's board theme, kept when games are destroyed
+	async fn get_theme(&mut self, guild_id_: i64) -> anyhow::Result<Theme>;
+
+	async fn set_theme(&mut self, guild_id_: i64, theme: Theme) -> anyhow::Result<()>;
 }
 
 #[serenity::async_trait]
@@ -74,7 +708,7 @@ impl DBGame for Transaction<'_, Sqlite> {
 		ctx_msg: Option<(&Context, &Message)>,
 	) -> anyhow::Result<GameServer> {
 		match sqlx::query!("SELECT * FROM game_servers WHERE guild_id = ?", guild_id_)
-			.fetch_one(self)
+			.fetch_one(&mut *self)
 			.await
 		{
 			Ok(game) => Ok(GameServer {
@@ -82,6 +716,13 @@ impl DBGame for Transaction<'_, Sqlite> {
 				name: game.name,
 				width: game.width as u8,
 				height: game.height as u8,
+				upgrade_cost: game.upgrade_cost as u8,
+				max_range: game.max_range as u8,
+				ram_damage: game.ram_damage as u8,
+				pickup_spawns: game.pickup_spawns as u8,
+				status: game.status.parse()?,
+				terrain: TerrainMap::default(),
+				pickups: Vec::new(),
 			}),
 			Err(reason) => {
 				if let Some((ctx, msg)) = ctx_msg {
@@ -92,6 +733,17 @@ impl DBGame for Transaction<'_, Sqlite> {
 		}
 	}
 
+	async fn get_game_board(
+		&mut self,
+		guild_id_: i64,
+		ctx_msg: Option<(&Context, &Message)>,
+	) -> anyhow::Result<GameServer> {
+		let mut game = self.get_game(guild_id_, ctx_msg).await?;
+		game.terrain = self.get_terrain(guild_id_, None).await?;
+		game.pickups = self.get_pickups(guild_id_).await?;
+		Ok(game)
+	}
+
 	async fn get_player(
 		&mut self,
 		guild_id_: i64,
@@ -109,6 +761,8 @@ impl DBGame for Transaction<'_, Sqlite> {
 			Ok(player) => Ok(GamePlayer {
 				guild_id: GuildId(player.guild_id as u64),
 				user_id: UserId(player.user_id as u64),
+				number: player.number as u16,
+				color: player.color.parse()?,
 				pos_x: player.pos_x as u8,
 				pos_y: player.pos_y as u8,
 				health: player.health as u8,
@@ -123,4 +777,528 @@ impl DBGame for Transaction<'_, Sqlite> {
 			}
 		}
 	}
+
+	async fn get_players(&mut self, guild_id_: i64) -> anyhow::Result<Vec<GamePlayer>> {
+		sqlx::query!(
+			"SELECT * FROM game_server_players WHERE guild_id = ? ORDER BY number",
+			guild_id_
+		)
+		.fetch_all(self)
+		.await?
+		.into_iter()
+		.map(|player| {
+			Ok(GamePlayer {
+				guild_id: GuildId(player.guild_id as u64),
+				user_id: UserId(player.user_id as u64),
+				number: player.number as u16,
+				color: player.color.parse()?,
+				pos_x: player.pos_x as u8,
+				pos_y: player.pos_y as u8,
+				health: player.health as u8,
+				actions: player.actions as u8,
+				range: player.range as u8,
+			})
+		})
+		.collect()
+	}
+
+	async fn log_event(
+		&mut self,
+		guild_id_: i64,
+		actor: Option<UserId>,
+		target: Option<UserId>,
+		kind: EventKind,
+		before: Option<String>,
+		after: Option<String>,
+	) -> anyhow::Result<()> {
+		let actor_id_ = actor.map(|u| u.0 as i64);
+		let target_id_ = target.map(|u| u.0 as i64);
+		let kind_ = kind.as_str();
+		let now = chrono::Utc::now().timestamp();
+		sqlx::query!(
+			"
+			INSERT INTO game_events
+			(guild_id, actor_id, target_id, kind, before, after, created_at)
+			VALUES (?, ?, ?, ?, ?, ?, ?)
+			",
+			guild_id_,
+			actor_id_,
+			target_id_,
+			kind_,
+			before,
+			after,
+			now
+		)
+		.execute(self)
+		.await?;
+		Ok(())
+	}
+
+	async fn get_events(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+		user_id_: Option<i64>,
+		kind: Option<EventKind>,
+		limit: i64,
+	) -> anyhow::Result<Vec<GameEvent>> {
+		let kind_ = kind.map(|k| k.as_str());
+		sqlx::query!(
+			"
+			SELECT id, actor_id, target_id, kind, before, after, created_at
+			FROM game_events
+			WHERE guild_id = ? AND history_id IS ?
+				AND (? IS NULL OR actor_id = ? OR target_id = ?)
+				AND (? IS NULL OR kind = ?)
+			ORDER BY id DESC
+			LIMIT ?
+			",
+			guild_id_,
+			history_id,
+			user_id_,
+			user_id_,
+			user_id_,
+			kind_,
+			kind_,
+			limit
+		)
+		.fetch_all(self)
+		.await?
+		.into_iter()
+		.map(|event| {
+			Ok(GameEvent {
+				id: event.id,
+				actor_id: event.actor_id.map(|u| UserId(u as u64)),
+				target_id: event.target_id.map(|u| UserId(u as u64)),
+				kind: event.kind.parse()?,
+				before: event.before,
+				after: event.after,
+				created_at: event.created_at,
+			})
+		})
+		.collect()
+	}
+
+	async fn tally_votes(&mut self, guild_id_: i64) -> anyhow::Result<Option<(UserId, i64)>> {
+		let tally = sqlx::query!(
+			r#"
+			SELECT v.target_id, COUNT(*) AS "votes!: i64"
+			FROM game_server_votes v
+			JOIN game_server_players p ON p.guild_id = v.guild_id AND p.user_id = v.target_id
+			WHERE v.guild_id = ? AND p.health > 0
+			GROUP BY v.target_id
+			ORDER BY 2 DESC
+			LIMIT 2
+			"#,
+			guild_id_
+		)
+		.fetch_all(&mut *self)
+		.await?;
+		sqlx::query!(
+			"DELETE FROM game_server_votes WHERE guild_id = ?",
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?;
+		let winner = match tally.as_slice() {
+			[] => return Ok(None),
+			[first, second] if first.votes == second.votes => return Ok(None),
+			[first, ..] => first,
+		};
+		let before = self.get_player(guild_id_, winner.target_id, None).await?;
+		sqlx::query!(
+			"UPDATE game_server_players SET actions = actions + 1 WHERE guild_id = ? AND user_id = ?",
+			guild_id_,
+			winner.target_id
+		)
+		.execute(&mut *self)
+		.await?;
+		let mut after = before.clone();
+		after.actions += 1;
+		self.log_event(
+			guild_id_,
+			None,
+			Some(before.user_id),
+			EventKind::Jury,
+			Some(before.state()),
+			Some(after.state()),
+		)
+		.await?;
+		Ok(Some((UserId(winner.target_id as u64), winner.votes)))
+	}
+
+	async fn check_winner(&mut self, guild_id_: i64) -> anyhow::Result<Option<UserId>> {
+		let living = sqlx::query_scalar!(
+			"SELECT user_id FROM game_server_players WHERE guild_id = ? AND health > 0 LIMIT 2",
+			guild_id_
+		)
+		.fetch_all(&mut *self)
+		.await?;
+		let winner_id = match living.as_slice() {
+			[winner_id] => *winner_id,
+			_ => return Ok(None),
+		};
+		let now = chrono::Utc::now().timestamp();
+		let result = sqlx::query!(
+			"UPDATE game_servers SET status = 'finished', winner_id = ?, finished_at = ? WHERE guild_id = ? AND status = 'running'",
+			winner_id,
+			now,
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?;
+		if result.rows_affected() == 0 {
+			return Ok(None);
+		}
+		self.log_event(
+			guild_id_,
+			None,
+			Some(UserId(winner_id as u64)),
+			EventKind::Status,
+			Some(GameStatus::Running.as_str().to_string()),
+			Some(GameStatus::Finished.as_str().to_string()),
+		)
+		.await?;
+		self.archive_game(guild_id_, GameStatus::Finished.as_str())
+			.await?;
+		Ok(Some(UserId(winner_id as u64)))
+	}
+
+	async fn get_history_game(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+	) -> anyhow::Result<(i64, GameServer)> {
+		let game = sqlx::query!(
+			"
+			SELECT id, guild_id, name, width, height FROM game_history
+			WHERE guild_id = ? AND (? IS NULL OR id = ?)
+			ORDER BY id DESC
+			LIMIT 1
+			",
+			guild_id_,
+			history_id,
+			history_id
+		)
+		.fetch_one(&mut *self)
+		.await?;
+		let terrain = self.get_terrain(guild_id_, Some(game.id)).await?;
+		Ok((
+			game.id,
+			GameServer {
+				guild_id: GuildId(game.guild_id as u64),
+				name: game.name,
+				width: game.width as u8,
+				height: game.height as u8,
+				// Settings are not archived, nothing can be done in a finished game anyway
+				upgrade_cost: 0,
+				max_range: 0,
+				ram_damage: 0,
+				pickup_spawns: 0,
+				status: GameStatus::Finished,
+				terrain,
+				// Whatever was left lying around isn't kept either
+				pickups: Vec::new(),
+			},
+		))
+	}
+
+	async fn archive_game(&mut self, guild_id_: i64, status: &str) -> anyhow::Result<i64> {
+		let now = chrono::Utc::now().timestamp();
+		let game_id = sqlx::query!(
+			"
+			INSERT INTO game_history
+			(guild_id, name, width, height, status, winner_id, created_at, finished_at)
+			SELECT guild_id, name, width, height, ?, winner_id, created_at, COALESCE(finished_at, ?)
+			FROM game_servers WHERE guild_id = ?
+			",
+			status,
+			now,
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?
+		.last_insert_rowid();
+		sqlx::query!(
+			"
+			INSERT INTO game_history_players
+			(game_id, user_id, placement, health, actions, range, color)
+			SELECT ?, user_id, ROW_NUMBER() OVER (ORDER BY health DESC, actions DESC, range DESC, user_id), health, actions, range, color
+			FROM game_server_players WHERE guild_id = ?
+			",
+			game_id,
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?;
+		sqlx::query!(
+			"UPDATE game_events SET history_id = ? WHERE guild_id = ? AND history_id IS NULL",
+			game_id,
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?;
+		sqlx::query!(
+			"UPDATE game_terrain SET history_id = ? WHERE guild_id = ? AND history_id IS NULL",
+			game_id,
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?;
+		Ok(game_id)
+	}
+
+	async fn get_colors(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+	) -> anyhow::Result<BTreeMap<UserId, PlayerColor>> {
+		let rows = match history_id {
+			Some(history_id) => {
+				sqlx::query!(
+					r#"SELECT user_id, color AS "color!" FROM game_history_players WHERE game_id = ? AND color IS NOT NULL"#,
+					history_id
+				)
+				.fetch_all(&mut *self)
+				.await?
+				.into_iter()
+				.map(|row| (row.user_id, row.color))
+				.collect::<Vec<_>>()
+			}
+			None => sqlx::query!(
+				"SELECT user_id, color FROM game_server_players WHERE guild_id = ?",
+				guild_id_
+			)
+			.fetch_all(&mut *self)
+			.await?
+			.into_iter()
+			.map(|row| (row.user_id, row.color))
+			.collect::<Vec<_>>(),
+		};
+		rows.into_iter()
+			.map(|(user_id, color)| Ok((UserId(user_id as u64), color.parse()?)))
+			.collect()
+	}
+
+	async fn get_terrain(
+		&mut self,
+		guild_id_: i64,
+		history_id: Option<i64>,
+	) -> anyhow::Result<TerrainMap> {
+		let rows = sqlx::query!(
+			"SELECT pos_x, pos_y, kind FROM game_terrain WHERE guild_id = ? AND history_id IS ?",
+			guild_id_,
+			history_id
+		)
+		.fetch_all(self)
+		.await?;
+		let mut terrain = TerrainMap::default();
+		for row in rows {
+			terrain.set(
+				row.pos_x as u8,
+				row.pos_y as u8,
+				row.kind.parse::<Terrain>()?,
+			);
+		}
+		Ok(terrain)
+	}
+
+	async fn set_terrain(&mut self, guild_id_: i64, terrain: &TerrainMap) -> anyhow::Result<()> {
+		sqlx::query!(
+			"DELETE FROM game_terrain WHERE guild_id = ? AND history_id IS NULL",
+			guild_id_
+		)
+		.execute(&mut *self)
+		.await?;
+		for ((x, y), kind) in terrain.iter() {
+			let kind_ = kind.as_str();
+			sqlx::query!(
+				"INSERT INTO game_terrain (guild_id, pos_x, pos_y, kind) VALUES (?, ?, ?, ?)",
+				guild_id_,
+				x,
+				y,
+				kind_
+			)
+			.execute(&mut *self)
+			.await?;
+		}
+		Ok(())
+	}
+
+	async fn get_pickups(&mut self, guild_id_: i64) -> anyhow::Result<Vec<Pickup>> {
+		let rows = sqlx::query!(
+			"SELECT pos_x, pos_y, kind FROM game_pickups WHERE guild_id = ?",
+			guild_id_
+		)
+		.fetch_all(self)
+		.await?;
+		rows.into_iter()
+			.map(|row| {
+				Ok(Pickup {
+					pos_x: row.pos_x as u8,
+					pos_y: row.pos_y as u8,
+					kind: row.kind.parse()?,
+				})
+			})
+			.collect()
+	}
+
+	async fn take_pickup(
+		&mut self,
+		guild_id_: i64,
+		x: u8,
+		y: u8,
+	) -> anyhow::Result<Option<PickupKind>> {
+		let kind = sqlx::query_scalar!(
+			"SELECT kind FROM game_pickups WHERE guild_id = ? AND pos_x = ? AND pos_y = ?",
+			guild_id_,
+			x,
+			y
+		)
+		.fetch_optional(&mut *self)
+		.await?;
+		let kind = match kind {
+			Some(kind) => kind.parse()?,
+			None => return Ok(None),
+		};
+		sqlx::query!(
+			"DELETE FROM game_pickups WHERE guild_id = ? AND pos_x = ? AND pos_y = ?",
+			guild_id_,
+			x,
+			y
+		)
+		.execute(&mut *self)
+		.await?;
+		Ok(Some(kind))
+	}
+
+	async fn spawn_pickups(&mut self, guild_id_: i64) -> anyhow::Result<Vec<Pickup>> {
+		let mut game = self.get_game_board(guild_id_, None).await?;
+		let players = self.get_players(guild_id_).await?;
+		let mut spawned = Vec::with_capacity(game.pickup_spawns as usize);
+		for _ in 0..game.pickup_spawns {
+			let (pos_x, pos_y) = match random_empty_tile(game.width, game.height, |x, y| {
+				game.is_empty_tile(&players, x, y)
+			}) {
+				Some(tile) => tile,
+				None => break,
+			};
+			let kind = PickupKind::ALL[rand::random::<usize>() % PickupKind::ALL.len()];
+			let kind_ = kind.as_str();
+			sqlx::query!(
+				"INSERT INTO game_pickups (guild_id, pos_x, pos_y, kind) VALUES (?, ?, ?, ?)",
+				guild_id_,
+				pos_x,
+				pos_y,
+				kind_
+			)
+			.execute(&mut *self)
+			.await?;
+			let pickup = Pickup { pos_x, pos_y, kind };
+			game.pickups.push(pickup.clone());
+			spawned.push(pickup);
+		}
+		Ok(spawned)
+	}
+
+	async fn get_theme(&mut self, guild_id_: i64) -> anyhow::Result<Theme> {
+		let theme = sqlx::query!(
+			"SELECT theme FROM guild_settings WHERE guild_id = ?",
+			guild_id_
+		)
+		.fetch_optional(self)
+		.await?;
+		match theme {
+			Some(row) => row.theme.parse(),
+			None => Ok(Theme::default()),
+		}
+	}
+
+	async fn set_theme(&mut self, guild_id_: i64, theme: Theme) -> anyhow::Result<()> {
+		let theme_ = theme.as_str();
+		sqlx::query!(
+			"
+			INSERT INTO guild_settings (guild_id, theme) VALUES (?, ?)
+			ON CONFLICT (guild_id) DO UPDATE SET theme = excluded.theme
+			",
+			guild_id_,
+			theme_
+		)
+		.execute(self)
+		.await?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn empty_tiles_have_nothing_on_them() {
+		let mut terrain = TerrainMap::default();
+		terrain.set(0, 0, Terrain::Wall);
+		terrain.set(1, 0, Terrain::Water);
+		terrain.set(2, 0, Terrain::Forest);
+		let game = GameServer {
+			guild_id: GuildId(1),
+			name: "Game".to_string(),
+			width: 8,
+			height: 8,
+			upgrade_cost: 3,
+			max_range: 3,
+			ram_damage: 0,
+			pickup_spawns: 1,
+			status: GameStatus::Running,
+			terrain,
+			pickups: vec![Pickup {
+				pos_x: 4,
+				pos_y: 0,
+				kind: PickupKind::Heart,
+			}],
+		};
+		let players = vec![GamePlayer {
+			guild_id: GuildId(1),
+			user_id: UserId(1),
+			number: 0,
+			color: PlayerColor::Red,
+			pos_x: 3,
+			pos_y: 0,
+			health: 0,
+			actions: 0,
+			range: 1,
+		}];
+		assert!(!game.is_empty_tile(&players, 0, 0));
+		assert!(!game.is_empty_tile(&players, 1, 0));
+		assert!(game.is_empty_tile(&players, 2, 0));
+		// Wrecks still take up their tile
+		assert!(!game.is_empty_tile(&players, 3, 0));
+		assert!(!game.is_empty_tile(&players, 4, 0));
+		assert!(game.is_empty_tile(&players, 5, 0));
+	}
+
+	fn pickup_event(kind: PickupKind, actions: u8) -> GameEvent {
+		GameEvent {
+			id: 1,
+			actor_id: Some(UserId(1)),
+			target_id: None,
+			kind: EventKind::Pickup,
+			before: Some(kind.as_str().to_string()),
+			after: Some(format!("3,4,{},{},1", MAX_HEALTH, actions)),
+			created_at: 0,
+		}
+	}
+
+	#[test]
+	fn pickup_events_name_their_kind() {
+		assert_eq!(
+			pickup_event(PickupKind::Heart, 0).describe(),
+			"<@1> picked up a heart at 3,4"
+		);
+		// Already at the most actions, so nothing about the state shows it was a crate
+		assert_eq!(
+			pickup_event(PickupKind::Crate, u8::MAX).describe(),
+			"<@1> picked up an action crate at 3,4"
+		);
+	}
 }
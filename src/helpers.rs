@@ -1,9 +1,11 @@
 use anyhow::Context as AnyHowContext;
+use rand::Rng;
 use serenity::client::Context;
 use serenity::framework::standard::macros::*;
 use serenity::framework::standard::{Args, CommandOptions, Reason};
 use serenity::model::channel::Message;
 use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::str::FromStr;
 
 #[serenity::async_trait]
@@ -70,6 +72,121 @@ async fn supply_check(
 	})
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum Resource {
+	Actions,
+	Health,
+}
+
+impl FromStr for Resource {
+	type Err = Cow<'static, str>;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.trim().to_lowercase().as_str() {
+			"a" | "action" | "actions" => Resource::Actions,
+			"h" | "hp" | "health" | "heart" | "hearts" => Resource::Health,
+			_ => {
+				return Err("invalid resource, must be actions or health".into());
+			}
+		})
+	}
+}
+
+impl Resource {
+	pub fn name(self) -> &'static str {
+		match self {
+			Resource::Actions => "action",
+			Resource::Health => "health",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoardFormat {
+	Png,
+	Svg,
+	Text,
+}
+
+impl FromStr for BoardFormat {
+	type Err = Cow<'static, str>;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.trim().to_lowercase().as_str() {
+			"png" | "image" => BoardFormat::Png,
+			"svg" | "vector" => BoardFormat::Svg,
+			"text" | "txt" => BoardFormat::Text,
+			_ => {
+				return Err("invalid board format, must be png, svg, or text".into());
+			}
+		})
+	}
+}
+
+/// How much of each kind of terrain `init` scatters over a new board
+#[derive(Debug, Clone, Copy)]
+pub enum MapStyle {
+	Open,
+	Mixed,
+	Woods,
+	Lakes,
+	Ruins,
+}
+
+impl FromStr for MapStyle {
+	type Err = Cow<'static, str>;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.trim().to_lowercase().as_str() {
+			"open" | "empty" | "none" => MapStyle::Open,
+			"mixed" | "random" => MapStyle::Mixed,
+			"woods" | "forest" => MapStyle::Woods,
+			"lakes" | "water" => MapStyle::Lakes,
+			"ruins" | "walls" => MapStyle::Ruins,
+			_ => {
+				return Err("invalid map, must be open, mixed, woods, lakes, or ruins".into());
+			}
+		})
+	}
+}
+
+impl MapStyle {
+	/// Fraction of the board covered by walls, water, forest, and hills in that order
+	pub fn fractions(self) -> [f32; 4] {
+		match self {
+			MapStyle::Open => [0.0, 0.0, 0.0, 0.0],
+			MapStyle::Mixed => [0.04, 0.04, 0.08, 0.04],
+			MapStyle::Woods => [0.0, 0.02, 0.2, 0.03],
+			MapStyle::Lakes => [0.0, 0.15, 0.05, 0.03],
+			MapStyle::Ruins => [0.12, 0.0, 0.04, 0.04],
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Setting {
+	UpgradeCost,
+	MaxRange,
+	RamDamage,
+	PickupSpawns,
+}
+
+impl FromStr for Setting {
+	type Err = Cow<'static, str>;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s.trim().to_lowercase().replace('_', "-").as_str() {
+			"upgrade-cost" | "upgrade" => Setting::UpgradeCost,
+			"max-range" | "range" => Setting::MaxRange,
+			"ram-damage" | "ramming" | "ram" => Setting::RamDamage,
+			"pickup-spawns" | "pickups" => Setting::PickupSpawns,
+			_ => {
+				return Err("invalid setting".into());
+			}
+		})
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Direction {
 	North,
@@ -119,12 +236,20 @@ impl FromStr for Direction {
 }
 
 impl Direction {
+	pub const ALL: [Direction; 8] = [
+		Direction::North,
+		Direction::East,
+		Direction::South,
+		Direction::West,
+		Direction::NorthEast,
+		Direction::SouthEast,
+		Direction::SouthWest,
+		Direction::NorthWest,
+	];
+
 	pub fn is_axial(self) -> bool {
 		use Direction::*;
-		match self {
-			North | East | South | West => true,
-			_ => false,
-		}
+		matches!(self, North | East | South | West)
 	}
 
 	pub fn as_offsets(self) -> (i8, i8) {
@@ -157,3 +282,124 @@ impl Direction {
 		Some(((x as i16 + ox as i16) as u8, (y as i16 + oy as i16) as u8))
 	}
 }
+
+/// Parses a path like `n n ne`, where a count like `3x` repeats the direction after it, either
+/// separately as in `3x east` or joined as in `3xe`
+pub fn parse_steps<'a>(
+	words: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<Direction>, Cow<'static, str>> {
+	let mut steps = Vec::new();
+	let mut count = None;
+	for word in words {
+		let word = word.trim().to_lowercase();
+		let direction = match word.split_once('x') {
+			Some((n, rest))
+				if count.is_none() && !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) =>
+			{
+				count = Some(n.parse::<u8>().map_err(|_| "step count too large")?);
+				if rest.is_empty() {
+					continue;
+				}
+				rest.parse::<Direction>()?
+			}
+			_ => word.parse::<Direction>()?,
+		};
+		for _ in 0..count.take().unwrap_or(1) {
+			steps.push(direction);
+		}
+	}
+	if count.is_some() {
+		return Err("a count must be followed by a direction".into());
+	}
+	if steps.is_empty() {
+		return Err("no directions given".into());
+	}
+	Ok(steps)
+}
+
+/// Picks a random tile that `is_free`, giving up after a few misses so a crowded board can't
+/// spin forever
+pub fn random_empty_tile(
+	width: u8,
+	height: u8,
+	is_free: impl Fn(u8, u8) -> bool,
+) -> Option<(u8, u8)> {
+	let mut rng = rand::thread_rng();
+	for _attempt in 0..32 {
+		let pos_x = rng.gen_range(0..width);
+		let pos_y = rng.gen_range(0..height);
+		if is_free(pos_x, pos_y) {
+			return Some((pos_x, pos_y));
+		}
+	}
+	None
+}
+
+/// Shortest path between two tiles moving in any of the 8 directions, never stepping on a tile
+/// that is `blocked`.  `None` if there is no way through.
+pub fn find_path(
+	from: (u8, u8),
+	to: (u8, u8),
+	width: u8,
+	height: u8,
+	blocked: impl Fn(u8, u8) -> bool,
+) -> Option<Vec<Direction>> {
+	if from == to {
+		return Some(Vec::new());
+	}
+	let index = |(x, y): (u8, u8)| y as usize * width as usize + x as usize;
+	let mut visited = vec![false; width as usize * height as usize];
+	let mut came_from = vec![None; width as usize * height as usize];
+	let mut queue = VecDeque::new();
+	visited[index(from)] = true;
+	queue.push_back(from);
+	while let Some(pos) = queue.pop_front() {
+		for &direction in Direction::ALL.iter() {
+			let next = match direction.offset_values(pos.0, pos.1, width, height) {
+				Some(next) => next,
+				None => continue,
+			};
+			if visited[index(next)] || blocked(next.0, next.1) {
+				continue;
+			}
+			visited[index(next)] = true;
+			came_from[index(next)] = Some((direction, pos));
+			if next == to {
+				let mut path = Vec::new();
+				let mut at = to;
+				while let Some((direction, previous)) = came_from[index(at)] {
+					path.push(direction);
+					at = previous;
+				}
+				path.reverse();
+				return Some(path);
+			}
+			queue.push_back(next);
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn random_empty_tile_only_picks_free_tiles() {
+		// Can give up before finding one of so few, but never settles for a taken tile
+		for _ in 0..100 {
+			if let Some((x, y)) = random_empty_tile(8, 8, |x, y| x >= 4 && y < 2) {
+				assert!((4..8).contains(&x) && y < 2);
+			}
+		}
+		for _ in 0..100 {
+			let (x, y) = random_empty_tile(8, 8, |_, _| true).unwrap();
+			assert!(x < 8 && y < 8);
+		}
+	}
+
+	#[test]
+	fn random_empty_tile_gives_up_on_a_full_board() {
+		assert_eq!(random_empty_tile(8, 8, |_, _| false), None);
+	}
+}
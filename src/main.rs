@@ -1,15 +1,18 @@
+mod avatars;
 mod db;
 mod helpers;
+mod render;
+mod scheduler;
+mod terrain;
 
 use helpers::*;
 
 use crate::db::*;
+use crate::render::{Region, RenderCache, RenderOptions};
+use crate::terrain::{Terrain, TerrainMap};
 use anyhow::Context as AnyHowContext;
-use image::png::PngEncoder;
-use image::{ColorType, RgbImage};
-use plotters::prelude::*;
-use plotters_backend::BackendColor;
-use rand::Rng;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame};
 use serenity::client::bridge::gateway::GatewayIntents;
 use serenity::framework::standard::macros::*;
 use serenity::framework::standard::{
@@ -21,7 +24,7 @@ use serenity::model::prelude::*;
 use serenity::prelude::*;
 use sqlx::SqlitePool;
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::time::Duration;
 
 #[tokio::main]
@@ -76,12 +79,18 @@ async fn main() {
 	let mut client = Client::builder(token)
 		.event_handler(Handler)
 		.framework(framework)
-		.type_map_insert::<DB>(db)
+		.type_map_insert::<DB>(db.clone())
+		.type_map_insert::<RenderCache>(Default::default())
 		.cache_update_timeout(Duration::from_secs(15))
 		.intents(GatewayIntents::all())
 		.await
 		.expect("unable to initialize the discord client");
 
+	tokio::spawn(scheduler::supply_scheduler(
+		client.cache_and_http.http.clone(),
+		db,
+	));
+
 	println!("Starting discord client");
 	if let Err(reason) = client.start().await {
 		eprintln!("error running discord client: {:?}", reason);
@@ -120,16 +129,14 @@ async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
 			}
 			_ => {}
 		},
-		DispatchError::Ratelimited(info) => {
-			// We notify them only once.
-			if info.is_first_try {
-				let _ = msg
-					.reply(
-						ctx,
-						format!("Try this again in {} seconds.", info.as_secs()),
-					)
-					.await;
-			}
+		// We notify them only once.
+		DispatchError::Ratelimited(info) if info.is_first_try => {
+			let _ = msg
+				.reply(
+					ctx,
+					format!("Try this again in {} seconds.", info.as_secs()),
+				)
+				.await;
 		}
 		DispatchError::CommandDisabled(_) => {}
 		DispatchError::BlockedUser => {}
@@ -189,6 +196,12 @@ async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
 	}
 }
 
+/// Most frames a replay GIF will have before it starts skipping events
+const REPLAY_MAX_FRAMES: usize = 150;
+
+/// Most squares a single move command can go
+const MOVE_MAX_STEPS: usize = 16;
+
 struct Handler;
 
 #[serenity::async_trait]
@@ -198,7 +211,10 @@ impl EventHandler for Handler {}
 #[prefixes("tank", "t")]
 #[summary = "Tank Game"]
 #[description = "Tank Game"]
-#[commands(ping, init, destroy, join, board, supply, move_)] // attack, give, vote
+#[commands(
+	ping, init, destroy, join, board, supply, move_, moveto, attack, give, vote, schedule, upgrade,
+	config, theme, start, pause, resume, end, history, log, replay, color
+)]
 struct TankGame;
 
 #[help]
@@ -229,11 +245,20 @@ async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
 }
 
 #[command]
-#[description("Initialize a new game")]
-#[usage("<game-name:Game> <width:16> <height:16>")]
+#[description(
+	"Initialize a new game.  The map decides what terrain is scattered over the board:
+ * `open`: Nothing, just an empty board
+ * `mixed`: A bit of everything
+ * `woods`: Mostly forest, which blocks shots past it
+ * `lakes`: Mostly water, which can be shot over but not driven through
+ * `ruins`: Mostly walls, which block both
+Hills give +1 range to a tank on them"
+)]
+#[usage("<game-name:Game> <width:16> <height:16> <map:open>")]
 #[example("\"Game Name\" 16 16")]
+#[example("\"Game Name\" 24 24 woods")]
 #[min_args(0)]
-#[max_args(3)]
+#[max_args(4)]
 #[required_permissions("ADMINISTRATOR")]
 #[only_in(guilds)]
 async fn init(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
@@ -253,14 +278,28 @@ async fn init(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	let height = args.single::<u8>().unwrap_or(16) as i16;
 	if width < 8 || height < 8 {
 		msg.reply(ctx, "Error: Minimum width*height is 8x8").await?;
+		return Ok(());
 	}
+	let map = if args.is_empty() {
+		MapStyle::Open
+	} else {
+		match args.single::<MapStyle>() {
+			Ok(map) => map,
+			Err(reason) => {
+				msg.reply(ctx, format!("Unknown map: {}", reason)).await?;
+				return Err(anyhow::anyhow!("unknown map").into());
+			}
+		}
+	};
 	let mut db = DB::begin(ctx).await?;
+	let now = chrono::Utc::now().timestamp();
 	let results = sqlx::query!(
-		"INSERT INTO game_servers (guild_id, name, width, height) VALUES (?, ?, ?, ?)",
+		"INSERT INTO game_servers (guild_id, name, width, height, created_at) VALUES (?, ?, ?, ?, ?)",
 		guild_id,
 		name,
 		width,
-		height
+		height,
+		now
 	)
 	.execute(&mut db)
 	.await;
@@ -273,9 +312,14 @@ async fn init(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 		.await?;
 		return Ok(());
 	}
+	let terrain = TerrainMap::generate(map, width as u8, height as u8);
+	db.set_terrain(guild_id, &terrain).await?;
 	msg.reply(
 		ctx,
-		format!("Created new game `{}` of size {}x{}", name, width, height),
+		format!(
+			"Created new game `{}` of size {}x{} with a {:?} map, players can now join until it is started",
+			name, width, height, map
+		),
 	)
 	.await?;
 	db.commit().await?;
@@ -313,15 +357,24 @@ async fn destroy(ctx: &Context, msg: &Message) -> CommandResult {
 	// }
 	// So doing this slower version instead
 	let mut db = DB::begin(ctx).await?;
-	let result = sqlx::query_scalar!("SELECT name FROM game_servers WHERE guild_id = ?", guild_id)
-		.fetch_one(&mut db)
-		.await;
+	let result = sqlx::query!(
+		"SELECT name, status FROM game_servers WHERE guild_id = ?",
+		guild_id
+	)
+	.fetch_one(&mut db)
+	.await;
 	if result.is_err() {
 		msg.reply(ctx, "No game exists to destroy").await?;
 		result?;
 		return Ok(());
 	}
-	let name: String = result.unwrap();
+	let game = result.unwrap();
+	let name = game.name;
+	// Finished games were already archived when they finished
+	if game.status != GameStatus::Finished.as_str() {
+		let history_id = db.archive_game(guild_id, "destroyed").await?;
+		println!("Archived game `{}` as history #{}", name, history_id);
+	}
 	if let Ok(res) = sqlx::query!("DELETE FROM game_servers WHERE guild_id = ?", guild_id)
 		.execute(&mut db)
 		.await
@@ -332,7 +385,7 @@ async fn destroy(ctx: &Context, msg: &Message) -> CommandResult {
 				format!("Failed to delete game, report to admin: {}", name),
 			)
 			.await?;
-			return Err("rows_affected is 0")?;
+			return Err("rows_affected is 0".into());
 		}
 		sqlx::query!(
 			"DELETE from game_server_players WHERE guild_id = ?",
@@ -340,7 +393,14 @@ async fn destroy(ctx: &Context, msg: &Message) -> CommandResult {
 		)
 		.execute(&mut db)
 		.await?;
+		sqlx::query!("DELETE from game_server_votes WHERE guild_id = ?", guild_id)
+			.execute(&mut db)
+			.await?;
+		sqlx::query!("DELETE from game_pickups WHERE guild_id = ?", guild_id)
+			.execute(&mut db)
+			.await?;
 		db.commit().await?;
+		RenderCache::invalidate(ctx, guild).await;
 		msg.reply(ctx, format!("Game destroyed: {}", name)).await?;
 	} else {
 		msg.reply(ctx, format!("Unable to destroy game: {}", name))
@@ -349,6 +409,134 @@ async fn destroy(ctx: &Context, msg: &Message) -> CommandResult {
 	Ok(())
 }
 
+#[command]
+#[description("Start the game, players can no longer join once it has started")]
+#[min_args(0)]
+#[max_args(0)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn start(ctx: &Context, msg: &Message) -> CommandResult {
+	change_status(ctx, msg, &[GameStatus::Lobby], GameStatus::Running, "start").await
+}
+
+#[command]
+#[description("Pause the game, no actions can be taken until it is resumed")]
+#[min_args(0)]
+#[max_args(0)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
+	change_status(
+		ctx,
+		msg,
+		&[GameStatus::Running],
+		GameStatus::Paused,
+		"pause",
+	)
+	.await
+}
+
+#[command]
+#[description("Resume a paused game")]
+#[min_args(0)]
+#[max_args(0)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
+	change_status(
+		ctx,
+		msg,
+		&[GameStatus::Paused],
+		GameStatus::Running,
+		"resume",
+	)
+	.await
+}
+
+#[command]
+#[description("End the game without destroying it, the final board can still be shown")]
+#[min_args(0)]
+#[max_args(0)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn end(ctx: &Context, msg: &Message) -> CommandResult {
+	change_status(
+		ctx,
+		msg,
+		&[GameStatus::Lobby, GameStatus::Running, GameStatus::Paused],
+		GameStatus::Finished,
+		"end",
+	)
+	.await
+}
+
+async fn change_status(
+	ctx: &Context,
+	msg: &Message,
+	from: &[GameStatus],
+	to: GameStatus,
+	doing: &str,
+) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game(guild_id_, Some((ctx, msg))).await?;
+	game.status.require(from, doing, Some((ctx, msg))).await?;
+	if game.status == GameStatus::Lobby && to == GameStatus::Running {
+		let players = sqlx::query_scalar!(
+			"SELECT COUNT(*) FROM game_server_players WHERE guild_id = ?",
+			guild_id_
+		)
+		.fetch_one(&mut db)
+		.await?;
+		if players < 2 {
+			msg.reply(ctx, "Need at least 2 players to start").await?;
+			return Err(anyhow::anyhow!("not enough players to start").into());
+		}
+	}
+	let status = to.as_str();
+	sqlx::query!(
+		"UPDATE game_servers SET status = ? WHERE guild_id = ?",
+		status,
+		guild_id_
+	)
+	.execute(&mut db)
+	.await?;
+	db.log_event(
+		guild_id_,
+		Some(msg.author.id),
+		None,
+		EventKind::Status,
+		Some(game.status.as_str().to_string()),
+		Some(status.to_string()),
+	)
+	.await?;
+	if to == GameStatus::Finished {
+		let now = chrono::Utc::now().timestamp();
+		sqlx::query!(
+			"UPDATE game_servers SET finished_at = ? WHERE guild_id = ?",
+			now,
+			guild_id_
+		)
+		.execute(&mut db)
+		.await?;
+		db.archive_game(guild_id_, status).await?;
+	}
+	db.commit().await?;
+	println!(
+		"Game `{}` in server {} changed from {} to {}",
+		game.name,
+		guild_id_,
+		game.status.as_str(),
+		status
+	);
+	msg.reply(
+		ctx,
+		format!("Game `{}` is now {}", game.name, to.describe()),
+	)
+	.await?;
+	Ok(())
+}
+
 #[command]
 #[description("Join the current game board")]
 #[min_args(0)]
@@ -378,34 +566,37 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 		return Ok(());
 	}
 
-	let game = if let Ok(game) = sqlx::query!(
-		"SELECT name, width, height FROM game_servers WHERE guild_id = ?",
-		guild_id_
-	)
-	.fetch_one(&mut DB::acquire(ctx).await?)
-	.await
-	{
-		game
-	} else {
-		msg.reply(ctx, "Game not in progress").await?;
-		return Ok(());
-	};
+	let game = db.get_game_board(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Lobby], "join", Some((ctx, msg)))
+		.await?;
 
-	let health = 3;
+	let health = MAX_HEALTH;
 	let actions = 0;
 	let range = 1;
 
-	for _attempt in 0..32 {
-		let pos_x = rand::thread_rng().gen_range(0..game.width);
-		let pos_y = rand::thread_rng().gen_range(0..game.height);
+	let number = sqlx::query_scalar!(
+		r#"SELECT COALESCE(MAX(number) + 1, 0) AS "number!: i64" FROM game_server_players WHERE guild_id = ?"#,
+		guild_id_
+	)
+	.fetch_one(&mut db)
+	.await?;
+	let color = PlayerColor::for_number(number as u16).as_str();
+	let players = db.get_players(guild_id_).await?;
+
+	if let Some((pos_x, pos_y)) = random_empty_tile(game.width, game.height, |x, y| {
+		game.is_empty_tile(&players, x, y)
+	}) {
 		let result = sqlx::query!(
 			"
 			INSERT INTO game_server_players
-			(guild_id, user_id, pos_x, pos_y, health, actions, range)
-			VALUES (?, ?, ?, ?, ?, ?, ?)
+			(guild_id, user_id, number, color, pos_x, pos_y, health, actions, range)
+			VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
 			",
 			guild_id_,
 			user_id_,
+			number,
+			color,
 			pos_x,
 			pos_y,
 			health,
@@ -416,6 +607,16 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 		.await;
 		match result {
 			Ok(v) if v.rows_affected() == 1 => {
+				let player = db.get_player(guild_id_, user_id_, None).await?;
+				db.log_event(
+					guild_id_,
+					Some(user_id),
+					None,
+					EventKind::Join,
+					None,
+					Some(player.state()),
+				)
+				.await?;
 				db.commit().await?;
 				println!(
 					"Successfully joined to game `{}`: `{:?}`",
@@ -434,9 +635,20 @@ async fn join(ctx: &Context, msg: &Message) -> CommandResult {
 }
 
 #[command]
-#[description("Show the game board")]
+#[description(
+	"Show the game board, or only part of it around a player or between two corners. Start with `text` for a text version, or `svg` for a scalable image"
+)]
+#[usage("<format:png> <\"me\"-or-player> <radius:3>")]
+#[usage("<format:png> <x1,y1> <x2,y2>")]
+#[example("")]
+#[example("me")]
+#[example("@SomeName 5")]
+#[example("0,0 7,7")]
+#[example("text")]
+#[example("text me 4")]
+#[example("svg")]
 #[min_args(0)]
-#[max_args(0)]
+#[max_args(5)]
 #[only_in(guilds)]
 #[bucket("ShowBoard")]
 async fn board(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
@@ -449,187 +661,184 @@ async fn board(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	};
 	let guild_id = guild.0 as i64;
 
-	let game = if let Ok(game) = sqlx::query!(
-		"SELECT name, width, height FROM game_servers WHERE guild_id = ?",
-		guild_id
-	)
-	.fetch_one(&mut DB::acquire(ctx).await?)
-	.await
-	{
-		game
-	} else {
-		msg.reply(ctx, "Game not in progress").await?;
-		return Ok(());
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game_board(guild_id, Some((ctx, msg))).await?;
+	let players = db.get_players(guild_id).await?;
+	let theme = db.get_theme(guild_id).await?;
+	// Only read from, so don't hold it open while talking to discord
+	drop(db);
+	let format = match args.current().map(|a| a.parse::<BoardFormat>()) {
+		Some(Ok(format)) => {
+			args.advance();
+			format
+		}
+		_ => BoardFormat::Png,
 	};
-
-	let players = sqlx::query!("SELECT user_id, pos_x, pos_y, health, actions, range FROM game_server_players WHERE guild_id = ? ORDER BY user_id", guild_id).fetch_all(&mut DB::acquire(ctx).await?).await?;
-
-	let tile_size = 25u32;
-	let image_width = game.width as u32 * tile_size + 1;
-	let image_height = game.height as u32 * tile_size + 1;
-	let mut image = RgbImage::new(image_width, image_height);
-	{
-		let mut image = BitMapBackend::with_buffer(&mut image, (image_width, image_height));
-		let image_width = image_width as i32;
-		let image_height = image_height as i32;
-		let tile_size = tile_size as i32;
-		let bg_style = BackendColor {
-			alpha: 1.0,
-			rgb: (255, 255, 255),
-		};
-		let line_style = BackendColor {
-			alpha: 1.0,
-			rgb: (0, 0, 0),
+	let mut options = RenderOptions {
+		theme,
+		..RenderOptions::default()
+	};
+	if !args.is_empty() {
+		let focus = if args.current().map(|a| a.eq_ignore_ascii_case("me")) == Some(true) {
+			Some(msg.author.id)
+		} else {
+			msg.mentions.first().map(|u| u.id)
 		};
-		let tank_health = [
-			BackendColor {
-				alpha: 0.5,
-				rgb: (196, 196, 196),
-			},
-			BackendColor {
-				alpha: 1.0,
-				rgb: (196, 0, 0),
-			},
-			BackendColor {
-				alpha: 1.0,
-				rgb: (196, 196, 0),
-			},
-			BackendColor {
-				alpha: 1.0,
-				rgb: (0, 196, 0),
-			},
-		];
-		let text_id_size = (tile_size * 2) / 3;
-		let text_id_style = &("sans-serif", text_id_size)
-			.into_text_style(&image.get_size())
-			.color(&BLACK);
-		let range_style = [
-			BackendColor {
-				alpha: 0.25,
-				rgb: (196, 196, 196),
-			},
-			BackendColor {
-				alpha: 0.25,
-				rgb: (196, 196, 0),
-			},
-			BackendColor {
-				alpha: 0.25,
-				rgb: (196, 0, 0),
+		options.region = Some(match focus {
+			Some(user_id) => {
+				args.advance();
+				let radius = args.single::<u8>().unwrap_or(3);
+				match players.iter().find(|p| p.user_id == user_id) {
+					Some(player) => Region::around(player.pos_x, player.pos_y, radius, &game),
+					None => {
+						msg.reply(ctx, "That player is not in the game").await?;
+						return Err(anyhow::anyhow!("board focus is not a player").into());
+					}
+				}
+			}
+			None => match (
+				args.single::<u8>(),
+				args.single::<u8>(),
+				args.single::<u8>(),
+				args.single::<u8>(),
+			) {
+				(Ok(x1), Ok(y1), Ok(x2), Ok(y2)) => Region::new(x1, y1, x2, y2, &game),
+				_ => {
+					msg.reply(ctx, "Must be `me`, a player, or two corners like `0,0 7,7`")
+						.await?;
+					return Err(anyhow::anyhow!("invalid board region").into());
+				}
 			},
-		];
-
-		// Board itself
-		image.draw_rect((1, 1), (image_width - 2, image_height - 2), &bg_style, true)?;
-		(0..game.width as i32).for_each(|x| {
-			let _ = image.draw_line(
-				(x * tile_size, 0),
-				(x * tile_size, image_height - 1),
-				&line_style,
-			);
-		});
-		(0..game.height as i32).for_each(|y| {
-			let _ = image.draw_line(
-				(0, y * tile_size),
-				(image_width - 1, y * tile_size),
-				&line_style,
-			);
 		});
+	}
+	let guild = ctx
+		.cache
+		.guild_field(guild, |g| g.members.clone())
+		.await
+		.context("Guild access missing")?;
+	let name = |user_id: &UserId| {
+		guild
+			.get(user_id)
+			.map(|m| m.user.name.clone())
+			.unwrap_or_else(|| format!("<@{}>", user_id))
+	};
 
-		// Range indicators
-		for range in (1..=3).into_iter().rev() {
-			for player in players.iter().filter(|p| p.range == range) {
-				let dist = range as i32 * tile_size + (tile_size / 3);
-				// Range
-				let c = (
-					player.pos_x as i32 * tile_size + (tile_size / 2),
-					player.pos_y as i32 * tile_size + (tile_size / 2),
-				);
-				image.draw_rect(
-					(c.0 - dist, c.1 - dist),
-					(c.0 + dist, c.1 + dist),
-					&range_style[range as usize - 1],
-					true,
-				)?;
+	if format == BoardFormat::Text {
+		let mut pages = vec![String::new()];
+		for line in render::render_text(&game, &players, options.region) {
+			let page = pages.last_mut().unwrap();
+			// Leave room for the code block fences
+			if !page.is_empty() && page.len() + line.len() + 8 > 2000 {
+				pages.push(String::new());
+			}
+			let page = pages.last_mut().unwrap();
+			page.push_str(&line);
+			page.push('\n');
+		}
+		if pages.len() > 5 {
+			msg.reply(
+				ctx,
+				"The board is too big to show as text, pick a part of it",
+			)
+			.await?;
+			return Err(anyhow::anyhow!("text board too long").into());
+		}
+		for page in pages {
+			msg.channel_id.say(ctx, format!("```\n{}```", page)).await?;
+		}
+		let mut key = vec!["Tanks are `number:health`, `x` for destroyed".to_string()];
+		if !game.terrain.is_empty() {
+			let symbols = Terrain::FEATURES
+				.iter()
+				.map(|t| format!("`{}` {}", t.symbol(), t.as_str()))
+				.collect::<Vec<_>>()
+				.join(", ");
+			key.push(format!("Terrain is {}", symbols));
+		}
+		if !game.pickups.is_empty() {
+			let symbols = PickupKind::ALL
+				.iter()
+				.map(|k| format!("`{}` {}", k.symbol(), k.as_str()))
+				.collect::<Vec<_>>()
+				.join(", ");
+			key.push(format!("Pickups are {}", symbols));
+		}
+		for p in players.iter() {
+			key.push(format!(
+				"{} {}: {} at {},{} with {} health, {} actions, range {}",
+				p.color.emoji(),
+				p.number,
+				name(&p.user_id),
+				p.pos_x,
+				p.pos_y,
+				p.health,
+				p.actions,
+				p.range
+			));
+		}
+		// Split the same way so a big game's key doesn't go over the message limit
+		let mut messages = vec![String::new()];
+		for line in key {
+			let message = messages.last_mut().unwrap();
+			if !message.is_empty() && message.len() + line.len() + 1 > 2000 {
+				messages.push(String::new());
 			}
+			let message = messages.last_mut().unwrap();
+			message.push_str(&line);
+			message.push('\n');
+		}
+		for message in messages {
+			msg.channel_id.say(ctx, message).await?;
 		}
+		return Ok(());
+	}
 
-		for (i, player) in players.iter().enumerate() {
-			dbg!((i, player));
-			// Health
-			let center = (
-				player.pos_x as i32 * tile_size + (tile_size / 2),
-				player.pos_y as i32 * tile_size + (tile_size / 2),
-			);
-			if player.health < 0 || player.health > 3 {
-				eprintln!("Invalid player data in game {}: {:?}", game.name, player);
-			} else {
-				image.draw_circle(
-					center,
-					tile_size as u32 / 3,
-					&tank_health[player.health as usize],
-					true,
-				)?;
+	let users = player_users(ctx, &players).await;
+	let avatars = users
+		.iter()
+		.map(|u| (u.id, u.avatar.clone()))
+		.collect::<Vec<_>>();
+	let key = RenderCache::key(&game, &players, &options, (format, avatars));
+	let data = match RenderCache::get(ctx, game.guild_id, key).await {
+		Some(data) => data,
+		None => {
+			// Not drawn in svg, so not worth downloading
+			if format != BoardFormat::Svg {
+				options.avatars = avatars::load_avatars(&users).await;
 			}
-			// Player ID#
-			let text_offset_x = if i < 10 { (3 * text_id_size) / 7 } else { 0 };
-			let center = (
-				player.pos_x as i32 * tile_size + 2 + text_offset_x,
-				player.pos_y as i32 * tile_size + 2,
-			);
-			image.draw_text(&i.to_string(), text_id_style, center)?;
-			// Player Actions
-			let text_offset_x = if player.actions < 10 {
-				(3 * text_id_size) / 7
+			let data = if format == BoardFormat::Svg {
+				render::render_svg(&game, &players, &options)?.into_bytes()
 			} else {
-				0
+				render::render_png(&game, &players, &options)?
 			};
-			let center = (
-				player.pos_x as i32 * tile_size + 2 + text_offset_x,
-				player.pos_y as i32 * tile_size + (tile_size / 2) + 1,
-			);
-			image.draw_text(&player.actions.to_string(), text_id_style, center)?;
+			RenderCache::insert(ctx, game.guild_id, key, data.clone()).await;
+			data
 		}
-	}
-
-	// Leaving off the 3 or 4 for color as it should compress smaller than that anyway
-	let mut data = Vec::with_capacity(image_width as usize * image_height as usize);
-	{
-		PngEncoder::new(&mut data).encode(
-			image.as_raw().as_slice(),
-			image_width,
-			image_height,
-			ColorType::Rgb8,
-		)?;
-	}
-
+	};
 	let now = chrono::Utc::now();
-	let guild = ctx
-		.cache
-		.guild_field(guild, |g| g.members.clone())
-		.await
-		.context("Guild access missing")?;
+	let filename = if format == BoardFormat::Svg {
+		format!("board-{}.svg", now.format("%s"))
+	} else {
+		format!("board-{}.png", now.format("%s"))
+	};
 	msg.channel_id
 		.send_message(ctx, |m| {
 			m
 				//.content("Current Board State")
 				.add_file(AttachmentType::Bytes {
 					data: Cow::Owned(data),
-					filename: format!("board-{}.png", now.format("%s")),
+					filename,
 				})
 				.embed(|e| {
-					e.title("Players").timestamp(now.to_rfc3339()).fields(
-						players.iter().enumerate().map(|(i, p)| {
-							let name = guild
-								.get(&UserId(p.user_id as u64))
-								.map(|m| m.user.name.clone())
-								.unwrap_or_else(|| format!("<@{}>", p.user_id));
+					e.title("Players")
+						.timestamp(now.to_rfc3339())
+						.fields(players.iter().map(|p| {
 							(
-								format!("{}: {}", i, name),
+								format!("{} {}: {}", p.color.emoji(), p.number, name(&p.user_id)),
 								format!("{}h {}a {}r", p.health, p.actions, p.range),
 								false,
 							)
-						}),
-					)
+						}))
 					//.description("Current State of the game Board")
 				})
 		})
@@ -637,6 +846,40 @@ async fn board(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	Ok(())
 }
 
+/// Name of a user for replies, without pinging them like a mention would
+async fn user_name(ctx: &Context, user_id: UserId) -> String {
+	match ctx.cache.user(user_id).await {
+		Some(user) => user.name,
+		None => format!("<@{}>", user_id),
+	}
+}
+
+/// Whichever of the players are in the cache, for their avatars
+async fn player_users(ctx: &Context, players: &[GamePlayer]) -> Vec<User> {
+	let mut users = Vec::with_capacity(players.len());
+	for player in players {
+		if let Some(user) = ctx.cache.user(player.user_id).await {
+			users.push(user);
+		}
+	}
+	users
+}
+
+/// Default render options along with the server's theme and the avatars of `players`
+async fn render_options(
+	ctx: &Context,
+	guild_id_: i64,
+	players: &[GamePlayer],
+) -> anyhow::Result<RenderOptions> {
+	let theme = DB::begin(ctx).await?.get_theme(guild_id_).await?;
+	let users = player_users(ctx, players).await;
+	Ok(RenderOptions {
+		avatars: avatars::load_avatars(&users).await,
+		theme,
+		..RenderOptions::default()
+	})
+}
+
 #[command]
 #[description("Supply action points to a player, max of 9 points at once, \"all\" for all players")]
 #[usage("<points:1>? <player-or-\"all\">+")]
@@ -651,6 +894,9 @@ async fn supply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	let guild_id_ = msg.guild_id.unwrap().0 as i64;
 	let mut db = DB::begin(ctx).await?;
 	let game = db.get_game(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Running], "supply", Some((ctx, msg)))
+		.await?;
 
 	let actions = args
 		.single::<i8>()
@@ -674,21 +920,64 @@ async fn supply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	// 	.for_each(|u| {
 	// 		dbg!(u);
 	// 	});
+	// Clamped the same in the database and the logged state so they agree, actions can't go below
+	// 0 or past what fits in a player's action count
 	if msg.mentions.is_empty() && args.current() == Some("all") {
+		let players = db.get_players(guild_id_).await?;
 		if let Ok(_success) = sqlx::query!(
-			"UPDATE game_server_players SET actions = actions + ? WHERE guild_id = ?",
+			"UPDATE game_server_players SET actions = MIN(MAX(actions + ?, 0), 255) WHERE guild_id = ? AND health > 0",
 			actions,
 			guild_id_
 		)
 		.execute(&mut db)
 		.await
 		{
+			for before in players.iter().filter(|p| p.health > 0) {
+				let mut after = before.clone();
+				after.actions = (after.actions as i16 + actions as i16).clamp(0, 255) as u8;
+				db.log_event(
+					guild_id_,
+					Some(msg.author.id),
+					Some(before.user_id),
+					EventKind::Supply,
+					Some(before.state()),
+					Some(after.state()),
+				)
+				.await?;
+			}
+			// Supplying everyone ends the voting period
+			let jury = match db.tally_votes(guild_id_).await? {
+				Some((winner, votes)) => format!(
+					", jury gave <@{}> an extra action with {} vote{}",
+					winner,
+					votes,
+					if votes == 1 { "" } else { "s" }
+				),
+				None => String::new(),
+			};
+			// Taking actions away shouldn't hand out anything
+			let pickups = if actions > 0 {
+				db.spawn_pickups(guild_id_).await?
+			} else {
+				Vec::new()
+			};
+			let pickups = if pickups.is_empty() {
+				String::new()
+			} else {
+				format!(
+					", {} pickup{} appeared on the board",
+					pickups.len(),
+					if pickups.len() == 1 { "" } else { "s" }
+				)
+			};
 			msg.reply(
 				ctx,
 				format!(
-					"Supply {} action{} to all is complete",
+					"Supply {} action{} to all is complete{}{}",
 					actions,
-					if actions == 0 { "" } else { "s" }
+					if actions == 0 { "" } else { "s" },
+					jury,
+					pickups
 				),
 			)
 			.await?;
@@ -701,8 +990,22 @@ async fn supply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 		let mut users_added = Vec::with_capacity(msg.mentions.len());
 		for u in msg.mentions.iter() {
 			let user_id_ = u.id.0 as i64;
-			match sqlx::query!("UPDATE game_server_players SET actions = actions + ? WHERE guild_id = ? AND user_id = ?", actions, guild_id_, user_id_).execute(&mut db).await {
+			let before = db.get_player(guild_id_, user_id_, None).await;
+			match sqlx::query!("UPDATE game_server_players SET actions = MIN(MAX(actions + ?, 0), 255) WHERE guild_id = ? AND user_id = ?", actions, guild_id_, user_id_).execute(&mut db).await {
 				Ok(r) if r.rows_affected() != 0 => {
+					if let Ok(before) = before {
+						let mut after = before.clone();
+						after.actions = (after.actions as i16 + actions as i16).clamp(0, 255) as u8;
+						db.log_event(
+							guild_id_,
+							Some(msg.author.id),
+							Some(u.id),
+							EventKind::Supply,
+							Some(before.state()),
+							Some(after.state()),
+						)
+						.await?;
+					}
 					users_added.push(u.name.as_str());
 				}
 				_error => {
@@ -725,61 +1028,1448 @@ async fn supply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 	Ok(())
 }
 
+#[command]
+#[description("Automatically supply actions to every living player on a schedule, posting a notice in this channel, \"off\" to stop, no arguments to show the current schedule")]
+#[usage("<utc-time:00:00> <every-hours:24> <actions:1>")]
+#[example("18:00")]
+#[example("06:00 12 2")]
+#[example("off")]
+#[min_args(0)]
+#[max_args(3)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn schedule(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game(guild_id_, Some((ctx, msg))).await?;
+
+	if args.is_empty() {
+		let current = sqlx::query!(
+			"SELECT supply_channel_id, supply_interval, supply_actions, supply_next FROM game_servers WHERE guild_id = ?",
+			guild_id_
+		)
+		.fetch_one(&mut db)
+		.await?;
+		match (
+			current.supply_channel_id,
+			current.supply_interval,
+			current.supply_next,
+		) {
+			(Some(channel_id), Some(interval), Some(next)) => {
+				msg.reply(
+					ctx,
+					format!(
+						"Supplying {} action{} every {}h in <#{}>, next supply <t:{}:R>",
+						current.supply_actions,
+						if current.supply_actions == 1 { "" } else { "s" },
+						interval / 3600,
+						channel_id,
+						next
+					),
+				)
+				.await?;
+			}
+			_ => {
+				msg.reply(ctx, "No supply schedule is set").await?;
+			}
+		}
+		return Ok(());
+	}
+
+	if args.current().map(|a| a.eq_ignore_ascii_case("off")) == Some(true) {
+		sqlx::query!(
+			"UPDATE game_servers SET supply_channel_id = NULL, supply_interval = NULL, supply_next = NULL WHERE guild_id = ?",
+			guild_id_
+		)
+		.execute(&mut db)
+		.await?;
+		db.commit().await?;
+		msg.reply(ctx, format!("Stopped scheduled supply for `{}`", game.name))
+			.await?;
+		return Ok(());
+	}
+
+	let at = match chrono::NaiveTime::parse_from_str(&args.single::<String>()?, "%H:%M") {
+		Ok(at) => at,
+		Err(reason) => {
+			msg.reply(ctx, "Invalid time, must be a UTC time like 18:00")
+				.await?;
+			return Err(reason.into());
+		}
+	};
+	let interval_hours = args.single::<u16>().unwrap_or(24).max(1);
+	let actions = args.single::<u8>().unwrap_or(1).min(9);
+	let channel_id_ = msg.channel_id.0 as i64;
+	let interval = interval_hours as i64 * 3600;
+	let next = scheduler::next_supply(at, interval_hours, chrono::Utc::now()).timestamp();
+	sqlx::query!(
+		"UPDATE game_servers SET supply_channel_id = ?, supply_interval = ?, supply_actions = ?, supply_next = ? WHERE guild_id = ?",
+		channel_id_,
+		interval,
+		actions,
+		next,
+		guild_id_
+	)
+	.execute(&mut db)
+	.await?;
+	db.commit().await?;
+	msg.reply(
+		ctx,
+		format!(
+			"Supplying {} action{} every {}h from {} UTC, next supply <t:{}:R>",
+			actions,
+			if actions == 1 { "" } else { "s" },
+			interval_hours,
+			at.format("%H:%M"),
+			next
+		),
+	)
+	.await?;
+	Ok(())
+}
+
 #[command("move")]
-#[description("Move a single direction in any of the 8 surrounding squares.  Format can be
+#[description("Move in any of the 8 surrounding squares, one action per square.  Several directions can be given to move along a path, and a count like `3x` repeats the direction after it.  The move stops early at a wall, another tank, or when out of actions.  Format can be
  * Like the keyboard number where 2 is down, 8 is up, 3 is lower-right, etc...
  * A direction name as a single character like r, l, u, or d, or dr for down-right, ul for up-left, etc...
  * A directional name like right/left/up/down/up-right/down-left/etc...
  * A cardinal direction as a single character like N for up, E for right, NW for up-left, SE for down-right, etc..
  * A cardinal direction as a full name like north, east, south, west, or north-east, south-west, etc...")]
-#[usage("<direction>{1,2}")]
+#[usage("<direction>{1,16}")]
 #[example("N")]
 #[example("up-right")]
 #[example("9")]
+#[example("n n ne")]
+#[example("3x east")]
 #[min_args(1)]
-#[max_args(1)]
+#[max_args(16)]
 #[only_in(guilds)]
 async fn move_(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-	let direction = match args.single::<Direction>() {
-		Ok(direction) => direction,
+	let words = args
+		.iter::<String>()
+		.filter_map(|a| a.ok())
+		.collect::<Vec<_>>();
+	let steps = match parse_steps(words.iter().map(String::as_str)) {
+		Ok(steps) => steps,
 		Err(reason) => {
 			msg.reply(ctx, format!("Invalid direction: {:?}", reason))
 				.await?;
-			return Err(anyhow::anyhow!("unsupported argument"))?;
+			return Err(anyhow::anyhow!("unsupported argument").into());
 		}
 	};
-	let guild_id_ = msg.guild_id.unwrap().0 as i64;
-	let user_id_ = msg.author.id.0 as i64;
-	let mut db = DB::begin(ctx).await?;
-	let game = db.get_game(guild_id_, Some((ctx, msg))).await?;
-	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
-	if player.actions == 0 {
-		msg.reply(ctx, "Out of actions, cannot move").await?;
-		return Err(anyhow::anyhow!("unable to move due to out of actions"))?;
-	}
-	let (pos_x, pos_y) =
-		match direction.offset_values(player.pos_x, player.pos_y, game.width, game.height) {
-			Some((x, y)) => (x, y),
-			None => {
-				msg.reply(ctx, "Cannot move past a wall").await?;
-				return Err(anyhow::anyhow!("cannot move past a wall"))?;
+	if steps.len() > MOVE_MAX_STEPS {
+		msg.reply(
+			ctx,
+			format!("Can only move up to {} squares at once", MOVE_MAX_STEPS),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("too many steps").into());
+	}
+	run_moves(ctx, msg, &steps, true).await
+}
+
+/// Moves the author along `steps`, stopping early at anything in the way, then shows the board.
+/// With ramming on and `ram` set, running into a tank damages it and ends the move instead.
+async fn run_moves(ctx: &Context, msg: &Message, steps: &[Direction], ram: bool) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game_board(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Running], "move", Some((ctx, msg)))
+		.await?;
+	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	if player.health == 0 {
+		msg.reply(ctx, "You have been destroyed, cannot move")
+			.await?;
+		return Err(anyhow::anyhow!("unable to move due to being destroyed").into());
+	}
+	let players = db.get_players(guild_id_).await?;
+	// Each step is logged on its own so replays follow the path
+	let mut current = player.clone();
+	let mut moved = 0;
+	let mut stopped = None;
+	let mut rammed = None;
+	let mut picked_up = Vec::new();
+	for direction in steps.iter() {
+		if current.actions == 0 {
+			stopped = Some("out of actions".to_string());
+			break;
+		}
+		let (pos_x, pos_y) =
+			match direction.offset_values(current.pos_x, current.pos_y, game.width, game.height) {
+				Some((x, y)) => (x, y),
+				None => {
+					stopped = Some("the edge of the board is in the way".to_string());
+					break;
+				}
+			};
+		let terrain = game.terrain.get(pos_x, pos_y);
+		if !terrain.is_passable() {
+			stopped = Some(format!("{} is in the way", terrain.describe()));
+			break;
+		}
+		if let Some(occupant) = players
+			.iter()
+			.find(|p| p.user_id != player.user_id && p.pos_x == pos_x && p.pos_y == pos_y)
+		{
+			// Wrecks are never rammed, there is nothing left to damage
+			if ram && game.ram_damage > 0 && occupant.health > 0 {
+				rammed = Some(occupant.clone());
+			} else {
+				stopped = Some(format!(
+					"{} is in the way",
+					user_name(ctx, occupant.user_id).await
+				));
 			}
-		};
+			break;
+		}
+		let mut after = current.clone();
+		after.actions -= 1;
+		after.pos_x = pos_x;
+		after.pos_y = pos_y;
+		db.log_event(
+			guild_id_,
+			Some(player.user_id),
+			None,
+			EventKind::Move,
+			Some(current.state()),
+			Some(after.state()),
+		)
+		.await?;
+		current = after;
+		moved += 1;
+		if let Some(kind) = db.take_pickup(guild_id_, pos_x, pos_y).await? {
+			let mut after = current.clone();
+			kind.apply(&mut after);
+			db.log_event(
+				guild_id_,
+				Some(player.user_id),
+				None,
+				EventKind::Pickup,
+				// What was picked up can't always be told from the change, such as a crate at
+				// the most actions, so it is logged in place of the state before like a status is
+				Some(kind.as_str().to_string()),
+				Some(after.state()),
+			)
+			.await?;
+			current = after;
+			picked_up.push(kind);
+		}
+	}
+	if moved == 0 && rammed.is_none() {
+		msg.reply(
+			ctx,
+			format!(
+				"Cannot move, {}",
+				stopped.unwrap_or_else(|| "no steps given".to_string())
+			),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("unable to move").into());
+	}
+
+	let mut rammed_after = None;
+	if let Some(victim) = rammed.as_ref() {
+		// Same as an attack, a destroyed tank's remaining actions go to whoever destroyed it
+		let mut after = victim.clone();
+		after.health = victim.health.saturating_sub(game.ram_damage);
+		let looted = if after.health == 0 { victim.actions } else { 0 };
+		after.actions -= looted;
+		let victim_id_ = victim.user_id.0 as i64;
+		sqlx::query!(
+			"UPDATE game_server_players SET health = ?, actions = ? WHERE guild_id = ? AND user_id = ?",
+			after.health,
+			after.actions,
+			guild_id_,
+			victim_id_
+		)
+		.execute(&mut db)
+		.await?;
+		current.actions = (current.actions - 1).saturating_add(looted);
+		db.log_event(
+			guild_id_,
+			Some(player.user_id),
+			Some(victim.user_id),
+			EventKind::Ram,
+			Some(victim.state()),
+			Some(after.state()),
+		)
+		.await?;
+		rammed_after = Some((after, looted));
+	}
+	sqlx::query!(
+		"UPDATE game_server_players SET health = ?, actions = ?, pos_x = ?, pos_y = ? WHERE guild_id = ? AND user_id = ?",
+		current.health,
+		current.actions,
+		current.pos_x,
+		current.pos_y,
+		guild_id_,
+		user_id_
+	)
+	.execute(&mut db)
+	.await?;
+	let winner = if rammed.is_some() {
+		db.check_winner(guild_id_).await?
+	} else {
+		None
+	};
+	db.commit().await?;
+	println!(
+		"Successfully moved {} in server {} to {}:{} in {} steps",
+		user_id_, guild_id_, current.pos_x, current.pos_y, moved
+	);
+
+	let reply = match (rammed, rammed_after, stopped) {
+		(Some(victim), Some((after, looted)), _) => {
+			let name = user_name(ctx, victim.user_id).await;
+			let rammed = if moved > 0 {
+				format!("Moved {} of {} squares then rammed", moved, steps.len())
+			} else {
+				"Rammed".to_string()
+			};
+			if after.health == 0 {
+				format!(
+					"{} and destroyed {}, looted {} action{}",
+					rammed,
+					name,
+					looted,
+					if looted == 1 { "" } else { "s" }
+				)
+			} else {
+				format!(
+					"{} {} for {} damage, {} health remaining",
+					rammed,
+					name,
+					victim.health - after.health,
+					after.health
+				)
+			}
+		}
+		(_, _, Some(reason)) => format!(
+			"Moved {} of {} squares, stopped as {}",
+			moved,
+			steps.len(),
+			reason
+		),
+		_ => "Successfully moved".to_string(),
+	};
+	let picked_up = if picked_up.is_empty() {
+		String::new()
+	} else {
+		format!(
+			", picked up {}",
+			picked_up
+				.iter()
+				.map(|kind| kind.describe())
+				.collect::<Vec<_>>()
+				.join(" and ")
+		)
+	};
+	msg.reply(ctx, format!("{}{}, showing board", reply, picked_up))
+		.await?;
+	if let Some(winner) = winner {
+		return announce_winner(ctx, msg, &game, winner).await;
+	}
+	board(ctx, msg, Args::new("", &[])).await?;
+	Ok(())
+}
+
+#[command]
+#[description("Move to a square by the shortest path around other tanks, walls, and water, one action per square.  Paths costing more than one action need to be confirmed with a reaction")]
+#[usage("<x,y>")]
+#[example("4,7")]
+#[min_args(2)]
+#[max_args(2)]
+#[only_in(guilds)]
+async fn moveto(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let (x, y) = match (args.single::<u8>(), args.single::<u8>()) {
+		(Ok(x), Ok(y)) => (x, y),
+		_ => {
+			msg.reply(ctx, "Must be a square like `4,7`").await?;
+			return Err(anyhow::anyhow!("invalid square").into());
+		}
+	};
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game_board(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Running], "move", Some((ctx, msg)))
+		.await?;
+	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	if player.health == 0 {
+		msg.reply(ctx, "You have been destroyed, cannot move")
+			.await?;
+		return Err(anyhow::anyhow!("unable to move due to being destroyed").into());
+	}
+	let players = db.get_players(guild_id_).await?;
+	// Only planning here, the move itself is checked again once it runs
+	drop(db);
+	if x >= game.width || y >= game.height {
+		msg.reply(
+			ctx,
+			format!("The board is only {}x{}", game.width, game.height),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("square off the board").into());
+	}
+	let terrain = game.terrain.get(x, y);
+	if !terrain.is_passable() {
+		msg.reply(ctx, format!("Cannot drive onto {}", terrain.describe()))
+			.await?;
+		return Err(anyhow::anyhow!("impassable square").into());
+	}
+	if let Some(occupant) = players
+		.iter()
+		.find(|p| p.user_id != player.user_id && p.pos_x == x && p.pos_y == y)
+	{
+		msg.reply(
+			ctx,
+			format!(
+				"{} is already there",
+				user_name(ctx, occupant.user_id).await
+			),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("square occupied").into());
+	}
+	let steps = match find_path(
+		(player.pos_x, player.pos_y),
+		(x, y),
+		game.width,
+		game.height,
+		|x, y| {
+			!game.terrain.get(x, y).is_passable()
+				|| players.iter().any(|p| p.pos_x == x && p.pos_y == y)
+		},
+	) {
+		Some(steps) if steps.is_empty() => {
+			msg.reply(ctx, "Already there").await?;
+			return Ok(());
+		}
+		Some(steps) => steps,
+		None => {
+			msg.reply(ctx, "No way through to there").await?;
+			return Err(anyhow::anyhow!("no path").into());
+		}
+	};
+	let cost = steps.len();
+	if cost > player.actions as usize {
+		msg.reply(
+			ctx,
+			format!(
+				"Getting there costs {} actions, only have {}",
+				cost, player.actions
+			),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("not enough actions for path").into());
+	}
+	if cost > 1 {
+		let prompt = msg
+			.reply(
+				ctx,
+				format!(
+					"Getting to {},{} costs {} actions, react with ✅ to move or ❌ to stay",
+					x, y, cost
+				),
+			)
+			.await?;
+		prompt.react(ctx, '✅').await?;
+		prompt.react(ctx, '❌').await?;
+		let reaction = prompt
+			.await_reaction(ctx)
+			.author_id(msg.author.id)
+			.filter(|r| r.emoji.unicode_eq("✅") || r.emoji.unicode_eq("❌"))
+			.timeout(Duration::from_secs(30))
+			.await;
+		match reaction {
+			Some(reaction) if reaction.as_inner_ref().emoji.unicode_eq("✅") => {}
+			_ => {
+				msg.reply(ctx, "Staying put").await?;
+				return Ok(());
+			}
+		}
+	}
+	// The path was planned around every tank, so anything in the way now moved there while
+	// waiting for the confirmation and was never agreed to be rammed
+	run_moves(ctx, msg, &steps, false).await
+}
+
+#[command]
+#[description("Attack another player within range, costs 1 action and deals 1 damage.  Destroying a player takes all of their remaining actions.")]
+#[usage("<player>")]
+#[example("@SomeName")]
+#[min_args(1)]
+#[max_args(1)]
+#[only_in(guilds)]
+async fn attack(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+	let target = if let Some(target) = msg.mentions.first() {
+		target
+	} else {
+		msg.reply(ctx, "Must mention the player to attack").await?;
+		return Err(anyhow::anyhow!("no attack target mentioned").into());
+	};
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let target_id_ = target.id.0 as i64;
+	if user_id_ == target_id_ {
+		msg.reply(ctx, "Cannot attack yourself").await?;
+		return Err(anyhow::anyhow!("cannot attack self").into());
+	}
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game_board(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Running], "attack", Some((ctx, msg)))
+		.await?;
+	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	if player.health == 0 {
+		msg.reply(ctx, "You have been destroyed, cannot attack")
+			.await?;
+		return Err(anyhow::anyhow!("unable to attack due to being destroyed").into());
+	}
+	if player.actions == 0 {
+		msg.reply(ctx, "Out of actions, cannot attack").await?;
+		return Err(anyhow::anyhow!("unable to attack due to out of actions").into());
+	}
+	let victim = match db.get_player(guild_id_, target_id_, None).await {
+		Ok(victim) => victim,
+		Err(reason) => {
+			msg.reply(ctx, format!("{} is not a current player", target.name))
+				.await?;
+			return Err(reason.into());
+		}
+	};
+	if victim.health == 0 {
+		msg.reply(ctx, format!("{} is already destroyed", target.name))
+			.await?;
+		return Err(anyhow::anyhow!("target is already destroyed").into());
+	}
+	require_in_reach(ctx, msg, &game, &player, &victim, &target.name).await?;
+
+	let health = victim.health as i64 - 1;
+	// A destroyed tank's remaining actions go to whoever destroyed it
+	let looted = if health == 0 {
+		victim.actions as i64
+	} else {
+		0
+	};
+	sqlx::query!(
+		"UPDATE game_server_players SET health = ?, actions = actions - ? WHERE guild_id = ? AND user_id = ?",
+		health,
+		looted,
+		guild_id_,
+		target_id_
+	)
+	.execute(&mut db)
+	.await?;
+	// Capped to what fits in a player's action count rather than wrapping around
 	sqlx::query!(
-		"UPDATE game_server_players SET actions = actions - 1, pos_x = ?, pos_y = ? WHERE guild_id = ? AND user_id = ?",
-		pos_x,
-		pos_y,
+		"UPDATE game_server_players SET actions = MIN(actions - 1 + ?, 255) WHERE guild_id = ? AND user_id = ?",
+		looted,
 		guild_id_,
 		user_id_
 	)
 	.execute(&mut db)
 	.await?;
+	let mut after = victim.clone();
+	after.health = health as u8;
+	after.actions -= looted as u8;
+	db.log_event(
+		guild_id_,
+		Some(player.user_id),
+		Some(victim.user_id),
+		EventKind::Attack,
+		Some(victim.state()),
+		Some(after.state()),
+	)
+	.await?;
+	let winner = db.check_winner(guild_id_).await?;
 	db.commit().await?;
 	println!(
-		"Successfully moved {} in server {} to {}:{}",
-		user_id_, guild_id_, pos_x, pos_y
+		"Successfully attacked {} by {} in server {} game `{}`, health now {}",
+		victim.user_id, player.user_id, game.guild_id, game.name, health
+	);
+	if health == 0 {
+		msg.reply(
+			ctx,
+			format!(
+				"Destroyed {}, looted {} action{}, showing board",
+				target.name,
+				looted,
+				if looted == 1 { "" } else { "s" }
+			),
+		)
+		.await?;
+	} else {
+		msg.reply(
+			ctx,
+			format!(
+				"Hit {}, {} health remaining, showing board",
+				target.name, health
+			),
+		)
+		.await?;
+	}
+	if let Some(winner) = winner {
+		return announce_winner(ctx, msg, &game, winner).await;
+	}
+	board(ctx, msg, Args::new("", &[])).await?;
+	Ok(())
+}
+
+/// Fails with a reply unless `other` is within range of `player` and nothing blocks the way
+async fn require_in_reach(
+	ctx: &Context,
+	msg: &Message,
+	game: &GameServer,
+	player: &GamePlayer,
+	other: &GamePlayer,
+	name: &str,
+) -> anyhow::Result<()> {
+	let distance = player.distance_to(other);
+	let range = game
+		.terrain
+		.range_at(player.pos_x, player.pos_y, player.range);
+	if distance > range {
+		msg.reply(
+			ctx,
+			format!(
+				"{} is out of range, {} tiles away but range is {}",
+				name, distance, range
+			),
+		)
+		.await?;
+		anyhow::bail!("target out of range");
+	}
+	if !game
+		.terrain
+		.line_of_sight((player.pos_x, player.pos_y), (other.pos_x, other.pos_y))
+	{
+		msg.reply(
+			ctx,
+			format!("{} is out of sight behind walls or forest", name),
+		)
+		.await?;
+		anyhow::bail!("target out of sight");
+	}
+	Ok(())
+}
+
+/// Posts the end of the game along with the final board
+async fn announce_winner(
+	ctx: &Context,
+	msg: &Message,
+	game: &GameServer,
+	winner: UserId,
+) -> CommandResult {
+	let guild_id_ = game.guild_id.0 as i64;
+	println!(
+		"Game `{}` in server {} won by {}",
+		game.name, guild_id_, winner
 	);
-	msg.reply(ctx, "Successfully moved, showing board").await?;
-	board(ctx, msg, args).await?;
+	let players = DB::begin(ctx).await?.get_players(guild_id_).await?;
+	let options = render_options(ctx, guild_id_, &players).await?;
+	let data = render::render_png(game, &players, &options)?;
+	msg.channel_id
+		.send_message(ctx, |m| {
+			m.content(format!(
+				"<@{}> is the last tank standing and wins `{}`!  Final board:",
+				winner, game.name
+			))
+			.add_file(AttachmentType::Bytes {
+				data: Cow::Owned(data),
+				filename: format!("final-board-{}.png", chrono::Utc::now().format("%s")),
+			})
+		})
+		.await?;
+	Ok(())
+}
+
+#[command]
+#[description("Give actions or health to another player within range, a destroyed player can be revived by giving them health")]
+#[usage("<player> <\"actions\"-or-\"health\":actions> <amount:1>")]
+#[example("@SomeName")]
+#[example("@SomeName health")]
+#[example("@SomeName actions 3")]
+#[min_args(1)]
+#[max_args(3)]
+#[only_in(guilds)]
+async fn give(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let target = if let Some(target) = msg.mentions.first() {
+		target
+	} else {
+		msg.reply(ctx, "Must mention the player to give to").await?;
+		return Err(anyhow::anyhow!("no give target mentioned").into());
+	};
+	args.advance();
+	let resource = args.single::<Resource>().unwrap_or(Resource::Actions);
+	let amount = match args.single::<u8>() {
+		Ok(amount) if amount > 0 => amount,
+		Ok(_) => {
+			msg.reply(ctx, "Must give at least 1").await?;
+			return Err(anyhow::anyhow!("give amount of 0").into());
+		}
+		Err(_) if args.is_empty() => 1,
+		Err(reason) => {
+			msg.reply(ctx, format!("Invalid amount: {}", reason))
+				.await?;
+			return Err(reason.into());
+		}
+	};
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let target_id_ = target.id.0 as i64;
+	if user_id_ == target_id_ {
+		msg.reply(ctx, "Cannot give to yourself").await?;
+		return Err(anyhow::anyhow!("cannot give to self").into());
+	}
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game_board(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Running], "give", Some((ctx, msg)))
+		.await?;
+	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	if player.health == 0 {
+		msg.reply(ctx, "You have been destroyed, cannot give")
+			.await?;
+		return Err(anyhow::anyhow!("unable to give due to being destroyed").into());
+	}
+	let receiver = match db.get_player(guild_id_, target_id_, None).await {
+		Ok(receiver) => receiver,
+		Err(reason) => {
+			msg.reply(ctx, format!("{} is not a current player", target.name))
+				.await?;
+			return Err(reason.into());
+		}
+	};
+	require_in_reach(ctx, msg, &game, &player, &receiver, &target.name).await?;
+
+	let amount_ = amount as i64;
+	let mut after = receiver.clone();
+	match resource {
+		Resource::Actions => {
+			if receiver.health == 0 {
+				msg.reply(
+					ctx,
+					format!("{} is destroyed, only health can be given", target.name),
+				)
+				.await?;
+				return Err(anyhow::anyhow!("cannot give actions to destroyed player").into());
+			}
+			if player.actions < amount {
+				msg.reply(ctx, format!("Only have {} actions to give", player.actions))
+					.await?;
+				return Err(anyhow::anyhow!("not enough actions to give").into());
+			}
+			sqlx::query!(
+				"UPDATE game_server_players SET actions = actions - ? WHERE guild_id = ? AND user_id = ?",
+				amount_,
+				guild_id_,
+				user_id_
+			)
+			.execute(&mut db)
+			.await?;
+			// Capped to what fits in a player's action count rather than wrapping around
+			sqlx::query!(
+				"UPDATE game_server_players SET actions = MIN(actions + ?, 255) WHERE guild_id = ? AND user_id = ?",
+				amount_,
+				guild_id_,
+				target_id_
+			)
+			.execute(&mut db)
+			.await?;
+			after.actions = after.actions.saturating_add(amount);
+		}
+		Resource::Health => {
+			// Giving away the last point of health would destroy the giver
+			if player.health <= amount {
+				msg.reply(
+					ctx,
+					format!("Only have {} health, must keep at least 1", player.health),
+				)
+				.await?;
+				return Err(anyhow::anyhow!("not enough health to give").into());
+			}
+			if receiver.health as u16 + amount as u16 > MAX_HEALTH as u16 {
+				msg.reply(
+					ctx,
+					format!(
+						"{} has {} health, cannot go above {}",
+						target.name, receiver.health, MAX_HEALTH
+					),
+				)
+				.await?;
+				return Err(anyhow::anyhow!("give would exceed max health").into());
+			}
+			sqlx::query!(
+				"UPDATE game_server_players SET health = health - ? WHERE guild_id = ? AND user_id = ?",
+				amount_,
+				guild_id_,
+				user_id_
+			)
+			.execute(&mut db)
+			.await?;
+			sqlx::query!(
+				"UPDATE game_server_players SET health = health + ? WHERE guild_id = ? AND user_id = ?",
+				amount_,
+				guild_id_,
+				target_id_
+			)
+			.execute(&mut db)
+			.await?;
+			after.health += amount;
+		}
+	}
+	db.log_event(
+		guild_id_,
+		Some(player.user_id),
+		Some(receiver.user_id),
+		EventKind::Give,
+		Some(receiver.state()),
+		Some(after.state()),
+	)
+	.await?;
+	db.commit().await?;
+	println!(
+		"Successfully gave {} {} from {} to {} in server {}",
+		amount,
+		resource.name(),
+		user_id_,
+		target_id_,
+		guild_id_
+	);
+	msg.reply(
+		ctx,
+		format!(
+			"Gave {} {}{} to {}",
+			amount,
+			resource.name(),
+			if amount != 1 && matches!(resource, Resource::Actions) {
+				"s"
+			} else {
+				""
+			},
+			target.name
+		),
+	)
+	.await?;
+	Ok(())
+}
+
+#[command]
+#[description("Destroyed players can vote once per supply period for a living player, whoever gets the most votes gets an extra action when everyone is next supplied")]
+#[usage("<player>")]
+#[example("@SomeName")]
+#[min_args(1)]
+#[max_args(1)]
+#[only_in(guilds)]
+async fn vote(ctx: &Context, msg: &Message) -> CommandResult {
+	let target = if let Some(target) = msg.mentions.first() {
+		target
+	} else {
+		msg.reply(ctx, "Must mention the player to vote for")
+			.await?;
+		return Err(anyhow::anyhow!("no vote target mentioned").into());
+	};
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let target_id_ = target.id.0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	db.get_game(guild_id_, Some((ctx, msg)))
+		.await?
+		.status
+		.require(&[GameStatus::Running], "vote", Some((ctx, msg)))
+		.await?;
+	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	if player.health != 0 {
+		msg.reply(ctx, "Only destroyed players can vote").await?;
+		return Err(anyhow::anyhow!("living player attempted to vote").into());
+	}
+	match db.get_player(guild_id_, target_id_, None).await {
+		Ok(candidate) if candidate.health > 0 => (),
+		Ok(_) => {
+			msg.reply(ctx, format!("{} is destroyed", target.name))
+				.await?;
+			return Err(anyhow::anyhow!("vote target is destroyed").into());
+		}
+		Err(reason) => {
+			msg.reply(ctx, format!("{} is not a current player", target.name))
+				.await?;
+			return Err(reason.into());
+		}
+	}
+	if sqlx::query!(
+		"INSERT INTO game_server_votes (guild_id, voter_id, target_id) VALUES (?, ?, ?)",
+		guild_id_,
+		user_id_,
+		target_id_
+	)
+	.execute(&mut db)
+	.await
+	.is_err()
+	{
+		msg.reply(ctx, "Already voted this period").await?;
+		return Ok(());
+	}
+	db.log_event(
+		guild_id_,
+		Some(msg.author.id),
+		Some(target.id),
+		EventKind::Vote,
+		None,
+		None,
+	)
+	.await?;
+	db.commit().await?;
+	println!(
+		"Successfully voted for {} by {} in server {}",
+		target_id_, user_id_, guild_id_
+	);
+	msg.reply(ctx, format!("Voted for {}", target.name)).await?;
+	Ok(())
+}
+
+#[command]
+#[description("Spend actions to increase your range by 1, see `config` for the cost and maximum")]
+#[min_args(0)]
+#[max_args(0)]
+#[only_in(guilds)]
+async fn upgrade(ctx: &Context, msg: &Message) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game(guild_id_, Some((ctx, msg))).await?;
+	game.status
+		.require(&[GameStatus::Running], "upgrade", Some((ctx, msg)))
+		.await?;
+	let player = db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	if player.health == 0 {
+		msg.reply(ctx, "You have been destroyed, cannot upgrade")
+			.await?;
+		return Err(anyhow::anyhow!("unable to upgrade due to being destroyed").into());
+	}
+	if player.range >= game.max_range {
+		msg.reply(
+			ctx,
+			format!("Already at the maximum range of {}", game.max_range),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("unable to upgrade past max range").into());
+	}
+	if player.actions < game.upgrade_cost {
+		msg.reply(
+			ctx,
+			format!(
+				"Upgrading range costs {} actions but only have {}",
+				game.upgrade_cost, player.actions
+			),
+		)
+		.await?;
+		return Err(anyhow::anyhow!("unable to upgrade due to out of actions").into());
+	}
+	let cost = game.upgrade_cost as i64;
+	sqlx::query!(
+		"UPDATE game_server_players SET actions = actions - ?, range = range + 1 WHERE guild_id = ? AND user_id = ?",
+		cost,
+		guild_id_,
+		user_id_
+	)
+	.execute(&mut db)
+	.await?;
+	let mut after = player.clone();
+	after.actions -= game.upgrade_cost;
+	after.range += 1;
+	db.log_event(
+		guild_id_,
+		Some(player.user_id),
+		None,
+		EventKind::Upgrade,
+		Some(player.state()),
+		Some(after.state()),
+	)
+	.await?;
+	db.commit().await?;
+	println!(
+		"Successfully upgraded {} in server {} to range {}",
+		user_id_,
+		guild_id_,
+		player.range + 1
+	);
+	msg.reply(
+		ctx,
+		format!(
+			"Upgraded range to {} for {} actions",
+			player.range + 1,
+			game.upgrade_cost
+		),
+	)
+	.await?;
+	Ok(())
+}
+
+#[command]
+#[description(
+	"Show the board theme of this server, or change it.  Themes are `light`, `dark`, `high-contrast`, and `colorblind-safe`"
+)]
+#[usage("<theme>")]
+#[example("dark")]
+#[example("colorblind-safe")]
+#[min_args(0)]
+#[max_args(1)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn theme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	if args.is_empty() {
+		let current = db.get_theme(guild_id_).await?;
+		let themes = Theme::ALL
+			.iter()
+			.map(|t| format!("`{}`", t.as_str()))
+			.collect::<Vec<_>>()
+			.join(", ");
+		msg.reply(
+			ctx,
+			format!(
+				"Board theme is `{}`, available themes are {}",
+				current.as_str(),
+				themes
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+	let theme = match args.single::<Theme>() {
+		Ok(theme) => theme,
+		Err(reason) => {
+			msg.reply(ctx, format!("Unknown theme: {}", reason)).await?;
+			return Err(anyhow::anyhow!("unknown theme").into());
+		}
+	};
+	db.set_theme(guild_id_, theme).await?;
+	db.commit().await?;
+	println!("Set theme of {} to {}", guild_id_, theme.as_str());
+	msg.reply(ctx, format!("Board theme set to `{}`", theme.as_str()))
+		.await?;
+	Ok(())
+}
+
+#[command]
+#[description(
+	"Show the game settings, or change one of them.  Settings are:
+ * `upgrade-cost`: Actions it costs to upgrade range by 1
+ * `max-range`: Highest range a tank can be upgraded to
+ * `ram-damage`: Damage done by moving into another tank, 0 to have tanks block the way instead
+ * `pickup-spawns`: Hearts and action crates placed on the board at each supply"
+)]
+#[usage("<setting> <value>")]
+#[example("upgrade-cost 2")]
+#[example("max-range 4")]
+#[example("ram-damage 1")]
+#[example("pickup-spawns 2")]
+#[min_args(0)]
+#[max_args(2)]
+#[required_permissions("ADMINISTRATOR")]
+#[only_in(guilds)]
+async fn config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	let game = db.get_game(guild_id_, Some((ctx, msg))).await?;
+	if args.is_empty() {
+		msg.reply(
+			ctx,
+			format!(
+				"Settings for `{}`:\n * `upgrade-cost`: {}\n * `max-range`: {}\n * `ram-damage`: {}\n * `pickup-spawns`: {}",
+				game.name, game.upgrade_cost, game.max_range, game.ram_damage, game.pickup_spawns
+			),
+		)
+		.await?;
+		return Ok(());
+	}
+	let setting = match args.single::<Setting>() {
+		Ok(setting) => setting,
+		Err(reason) => {
+			msg.reply(ctx, format!("Unknown setting: {}", reason))
+				.await?;
+			return Err(anyhow::anyhow!("unknown setting").into());
+		}
+	};
+	let value = match args.single::<u8>() {
+		Ok(value) => value,
+		Err(reason) => {
+			msg.reply(ctx, "Value must be a number from 0 to 255")
+				.await?;
+			return Err(reason.into());
+		}
+	};
+	let value_ = value as i64;
+	match setting {
+		Setting::UpgradeCost => {
+			sqlx::query!(
+				"UPDATE game_servers SET upgrade_cost = ? WHERE guild_id = ?",
+				value_,
+				guild_id_
+			)
+			.execute(&mut db)
+			.await?;
+		}
+		Setting::MaxRange => {
+			if value == 0 {
+				msg.reply(ctx, "Maximum range must be at least 1").await?;
+				return Err(anyhow::anyhow!("max range of 0").into());
+			}
+			sqlx::query!(
+				"UPDATE game_servers SET max_range = ? WHERE guild_id = ?",
+				value_,
+				guild_id_
+			)
+			.execute(&mut db)
+			.await?;
+		}
+		Setting::RamDamage => {
+			sqlx::query!(
+				"UPDATE game_servers SET ram_damage = ? WHERE guild_id = ?",
+				value_,
+				guild_id_
+			)
+			.execute(&mut db)
+			.await?;
+		}
+		Setting::PickupSpawns => {
+			sqlx::query!(
+				"UPDATE game_servers SET pickup_spawns = ? WHERE guild_id = ?",
+				value_,
+				guild_id_
+			)
+			.execute(&mut db)
+			.await?;
+		}
+	}
+	db.commit().await?;
+	msg.reply(ctx, format!("Set {:?} to {}", setting, value))
+		.await?;
+	Ok(())
+}
+
+#[command]
+#[description("List the past games in this server, or show the final standings of one of them")]
+#[usage("<history-id>")]
+#[example("")]
+#[example("3")]
+#[min_args(0)]
+#[max_args(1)]
+#[only_in(guilds)]
+async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let mut db = DB::acquire(ctx).await?;
+
+	if args.is_empty() {
+		let games = sqlx::query!(
+			r#"
+			SELECT h.id, h.name, h.width, h.height, h.status, h.winner_id, h.finished_at,
+				(SELECT COUNT(*) FROM game_history_players p WHERE p.game_id = h.id) AS "players!: i64"
+			FROM game_history h
+			WHERE h.guild_id = ?
+			ORDER BY h.id DESC
+			LIMIT 10
+			"#,
+			guild_id_
+		)
+		.fetch_all(&mut db)
+		.await?;
+		if games.is_empty() {
+			msg.reply(ctx, "No past games in this server").await?;
+			return Ok(());
+		}
+		let description = games
+			.iter()
+			.map(|g| {
+				format!(
+					"`#{}` **{}** {}x{}, {} players, {} <t:{}:d>{}",
+					g.id,
+					g.name,
+					g.width,
+					g.height,
+					g.players,
+					g.status,
+					g.finished_at,
+					g.winner_id
+						.map(|w| format!(", won by <@{}>", w))
+						.unwrap_or_default()
+				)
+			})
+			.collect::<Vec<_>>()
+			.join("\n");
+		msg.channel_id
+			.send_message(ctx, |m| {
+				m.embed(|e| e.title("Past Games").description(description))
+			})
+			.await?;
+		return Ok(());
+	}
+
+	let history_id = match args.single::<i64>() {
+		Ok(history_id) => history_id,
+		Err(reason) => {
+			msg.reply(ctx, "History id must be a number").await?;
+			return Err(reason.into());
+		}
+	};
+	let game = match sqlx::query!(
+		"SELECT * FROM game_history WHERE id = ? AND guild_id = ?",
+		history_id,
+		guild_id_
+	)
+	.fetch_one(&mut db)
+	.await
+	{
+		Ok(game) => game,
+		Err(reason) => {
+			msg.reply(ctx, format!("No past game #{} in this server", history_id))
+				.await?;
+			return Err(reason.into());
+		}
+	};
+	let players = sqlx::query!(
+		"SELECT user_id, placement, health, actions, range FROM game_history_players WHERE game_id = ? ORDER BY placement",
+		history_id
+	)
+	.fetch_all(&mut db)
+	.await?;
+	let mut description = format!(
+		"{}x{} board, {} <t:{}:f>",
+		game.width, game.height, game.status, game.finished_at
+	);
+	if let Some(created_at) = game.created_at {
+		description += &format!(", created <t:{}:f>", created_at);
+	}
+	if let Some(winner_id) = game.winner_id {
+		description += &format!("\nWon by <@{}>", winner_id);
+	}
+	msg.channel_id
+		.send_message(ctx, |m| {
+			m.embed(|e| {
+				e.title(format!("#{} {}", game.id, game.name))
+					.description(description)
+					// Discord only allows 25 fields in an embed
+					.fields(players.iter().take(25).map(|p| {
+						(
+							format!("{}.", p.placement),
+							format!("<@{}> {}h {}a {}r", p.user_id, p.health, p.actions, p.range),
+							false,
+						)
+					}))
+			})
+		})
+		.await?;
+	Ok(())
+}
+
+#[command]
+#[description("Show the most recent events in the current game, optionally only the ones involving a player or of one kind.  Kinds are join, move, attack, ram, give, supply, upgrade, vote, jury, status and pickup.")]
+#[usage("<count:10> <player>? <kind>?")]
+#[example("")]
+#[example("@SomeName")]
+#[example("25 attack")]
+#[example("@SomeName attack")]
+#[min_args(0)]
+#[max_args(3)]
+#[only_in(guilds)]
+async fn log(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let mut count = 10u8;
+	let mut kind = None;
+	for arg in args.iter::<String>().flatten() {
+		if let Ok(c) = arg.parse::<u8>() {
+			count = c.clamp(1, 100);
+		} else if let Ok(k) = arg.to_lowercase().parse::<EventKind>() {
+			kind = Some(k);
+		} else if !arg.starts_with("<@") {
+			msg.reply(ctx, format!("Unknown count, player or kind: {}", arg))
+				.await?;
+			return Err(anyhow::anyhow!("unknown log argument").into());
+		}
+	}
+	let user_id_ = msg.mentions.first().map(|u| u.id.0 as i64);
+
+	let mut db = DB::begin(ctx).await?;
+	db.get_game(guild_id_, Some((ctx, msg))).await?;
+	let events = db
+		.get_events(guild_id_, None, user_id_, kind, count as i64)
+		.await?;
+	if events.is_empty() {
+		msg.reply(ctx, "No matching events in this game").await?;
+		return Ok(());
+	}
+
+	// Discord caps an embed description at 4096 characters so split it over more than one
+	let mut pages = vec![String::new()];
+	for event in events.iter().rev() {
+		let line = format!(
+			"`#{}` <t:{}:f> {}\n",
+			event.id,
+			event.created_at,
+			event.describe()
+		);
+		if pages.last().unwrap().len() + line.len() > 4000 {
+			pages.push(String::new());
+		}
+		pages.last_mut().unwrap().push_str(&line);
+	}
+	let page_count = pages.len();
+	for (i, page) in pages.into_iter().enumerate() {
+		msg.channel_id
+			.send_message(ctx, |m| {
+				m.embed(|e| {
+					if page_count > 1 {
+						e.title(format!("Game Log ({}/{})", i + 1, page_count));
+					} else {
+						e.title("Game Log");
+					}
+					e.description(page)
+				})
+			})
+			.await?;
+	}
+	Ok(())
+}
+
+#[command]
+#[description("Render the history of a game as an animated GIF, defaults to the current game or else the most recently finished one")]
+#[usage("<history-id>?")]
+#[example("")]
+#[example("3")]
+#[min_args(0)]
+#[max_args(1)]
+#[only_in(guilds)]
+#[bucket("ShowBoard")]
+async fn replay(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let guild_id = msg.guild_id.unwrap();
+	let guild_id_ = guild_id.0 as i64;
+	let history_id = if args.is_empty() {
+		None
+	} else {
+		match args.single::<i64>() {
+			Ok(history_id) => Some(history_id),
+			Err(reason) => {
+				msg.reply(ctx, "History id must be a number").await?;
+				return Err(reason.into());
+			}
+		}
+	};
+	let mut db = DB::begin(ctx).await?;
+	let (mut game, history_id) = match (history_id, db.get_game_board(guild_id_, None).await) {
+		(None, Ok(game)) if game.status != GameStatus::Finished => (game, None),
+		(history_id, _) => match db.get_history_game(guild_id_, history_id).await {
+			Ok((history_id, game)) => (game, Some(history_id)),
+			Err(reason) => {
+				msg.reply(ctx, "No game found to replay").await?;
+				return Err(reason.into());
+			}
+		},
+	};
+	let events = db.get_events(guild_id_, history_id, None, None, -1).await?;
+	let colors = db.get_colors(guild_id_, history_id).await?;
+	drop(db);
+	// Pickups aren't logged when they spawn, so the ones on the board now would be wrong for
+	// every earlier frame
+	game.pickups.clear();
+
+	let mut players = BTreeMap::new();
+	let mut states = Vec::new();
+	for event in events.iter().rev() {
+		if event.apply(guild_id, &mut players, &colors) {
+			states.push(players.values().cloned().collect::<Vec<_>>());
+		}
+	}
+	if states.is_empty() {
+		msg.reply(ctx, "Nothing has happened in that game yet")
+			.await?;
+		return Ok(());
+	}
+	// Long games are sampled down so the upload stays a reasonable size, always keeping the end
+	let step = (states.len() as f32 / REPLAY_MAX_FRAMES as f32).max(1.0);
+	let last = states.len() - 1;
+	let mut indices = (0..REPLAY_MAX_FRAMES)
+		.map(|i| (i as f32 * step) as usize)
+		.take_while(|i| *i < last)
+		.collect::<Vec<_>>();
+	indices.push(last);
+
+	let name = game.name.clone();
+	let options = render_options(ctx, guild_id_, &states[last]).await?;
+	let data = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<u8>> {
+		let mut data = Vec::new();
+		{
+			let mut encoder = GifEncoder::new_with_speed(&mut data, 10);
+			encoder.set_repeat(Repeat::Infinite)?;
+			for (n, &i) in indices.iter().enumerate() {
+				let image = render::draw_board(&game, &states[i], &options)?;
+				let delay = if n == indices.len() - 1 { 3000 } else { 500 };
+				encoder.encode_frame(Frame::from_parts(
+					DynamicImage::ImageRgb8(image).into_rgba8(),
+					0,
+					0,
+					Delay::from_numer_denom_ms(delay, 1),
+				))?;
+			}
+		}
+		Ok(data)
+	})
+	.await??;
+
+	let now = chrono::Utc::now();
+	msg.channel_id
+		.send_message(ctx, |m| {
+			m.content(format!("Replay of `{}`", name))
+				.add_file(AttachmentType::Bytes {
+					data: Cow::Owned(data),
+					filename: format!("replay-{}.gif", now.format("%s")),
+				})
+		})
+		.await?;
+	Ok(())
+}
+
+#[command]
+#[description("Choose the color your tank is outlined in on the board, one of red, orange, yellow, green, blue, purple or brown")]
+#[usage("<color>")]
+#[example("blue")]
+#[min_args(1)]
+#[max_args(1)]
+#[only_in(guilds)]
+async fn color(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+	let color = match args.single::<String>()?.parse::<PlayerColor>() {
+		Ok(color) => color,
+		Err(reason) => {
+			msg.reply(
+				ctx,
+				"Color must be one of red, orange, yellow, green, blue, purple or brown",
+			)
+			.await?;
+			return Err(reason.into());
+		}
+	};
+	let guild_id_ = msg.guild_id.unwrap().0 as i64;
+	let user_id_ = msg.author.id.0 as i64;
+	let mut db = DB::begin(ctx).await?;
+	db.get_player(guild_id_, user_id_, Some((ctx, msg))).await?;
+	let color_ = color.as_str();
+	sqlx::query!(
+		"UPDATE game_server_players SET color = ? WHERE guild_id = ? AND user_id = ?",
+		color_,
+		guild_id_,
+		user_id_
+	)
+	.execute(&mut db)
+	.await?;
+	db.commit().await?;
+	msg.reply(
+		ctx,
+		format!("Your tank is now {} {}", color.emoji(), color_),
+	)
+	.await?;
 	Ok(())
 }
@@ -0,0 +1,963 @@
+use crate::db::*;
+use crate::terrain::Terrain;
+use image::imageops::FilterType;
+use image::png::PngEncoder;
+use image::{ColorType, RgbImage, RgbaImage};
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use plotters_backend::{BackendColor, BackendTextStyle};
+use serenity::client::Context;
+use serenity::model::id::{GuildId, UserId};
+use serenity::prelude::TypeMapKey;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+const fn color(alpha: f64, rgb: (u8, u8, u8)) -> BackendColor {
+	BackendColor { alpha, rgb }
+}
+
+/// The colors a theme draws the board with
+#[derive(Clone, Copy)]
+pub struct Palette {
+	/// Behind the tiles
+	pub board: BackendColor,
+	/// Around the tiles, behind the labels and the legend
+	pub page: BackendColor,
+	pub line: BackendColor,
+	pub text: RGBColor,
+	/// Indexed by health
+	pub tank_health: [BackendColor; MAX_HEALTH as usize + 1],
+	/// Indexed by range - 1, anything past the end uses the last one
+	pub range: [BackendColor; 3],
+	/// Indexed like `Terrain::FEATURES`
+	pub terrain: [BackendColor; 4],
+	/// Indexed like `PickupKind::ALL`
+	pub pickup: [BackendColor; 2],
+}
+
+impl Palette {
+	pub fn terrain(&self, terrain: Terrain) -> Option<&BackendColor> {
+		Terrain::FEATURES
+			.iter()
+			.position(|t| *t == terrain)
+			.map(|i| &self.terrain[i])
+	}
+
+	pub fn pickup(&self, kind: PickupKind) -> &BackendColor {
+		let i = PickupKind::ALL.iter().position(|k| *k == kind).unwrap_or(0);
+		&self.pickup[i]
+	}
+
+	pub fn of(theme: Theme) -> Self {
+		match theme {
+			Theme::Light => Palette {
+				board: color(1.0, (255, 255, 255)),
+				page: color(1.0, (255, 255, 255)),
+				line: color(1.0, (0, 0, 0)),
+				text: RGBColor(0, 0, 0),
+				tank_health: [
+					color(0.5, (196, 196, 196)),
+					color(1.0, (196, 0, 0)),
+					color(1.0, (196, 196, 0)),
+					color(1.0, (0, 196, 0)),
+				],
+				range: [
+					color(0.25, (196, 196, 196)),
+					color(0.25, (196, 196, 0)),
+					color(0.25, (196, 0, 0)),
+				],
+				terrain: [
+					color(1.0, (96, 96, 96)),
+					color(1.0, (128, 176, 232)),
+					color(1.0, (84, 156, 84)),
+					color(1.0, (212, 184, 136)),
+				],
+				pickup: [color(1.0, (220, 40, 90)), color(1.0, (176, 120, 56))],
+			},
+			// Matched to discord's own dark mode so it doesn't glare in the channel
+			Theme::Dark => Palette {
+				board: color(1.0, (47, 49, 54)),
+				page: color(1.0, (54, 57, 63)),
+				line: color(1.0, (142, 146, 151)),
+				text: RGBColor(220, 221, 222),
+				tank_health: [
+					color(0.5, (114, 118, 125)),
+					color(1.0, (237, 66, 69)),
+					color(1.0, (250, 168, 26)),
+					color(1.0, (87, 242, 135)),
+				],
+				range: [
+					color(0.2, (185, 187, 190)),
+					color(0.25, (250, 168, 26)),
+					color(0.25, (237, 66, 69)),
+				],
+				terrain: [
+					color(1.0, (24, 25, 28)),
+					color(1.0, (44, 82, 130)),
+					color(1.0, (38, 84, 50)),
+					color(1.0, (112, 94, 66)),
+				],
+				pickup: [color(1.0, (235, 69, 158)), color(1.0, (196, 140, 80))],
+			},
+			Theme::HighContrast => Palette {
+				board: color(1.0, (0, 0, 0)),
+				page: color(1.0, (0, 0, 0)),
+				line: color(1.0, (255, 255, 255)),
+				text: RGBColor(255, 255, 255),
+				tank_health: [
+					color(1.0, (96, 96, 96)),
+					color(1.0, (255, 0, 0)),
+					color(1.0, (255, 255, 0)),
+					color(1.0, (0, 255, 0)),
+				],
+				range: [
+					color(0.35, (255, 255, 255)),
+					color(0.35, (255, 255, 0)),
+					color(0.35, (255, 0, 255)),
+				],
+				terrain: [
+					color(1.0, (160, 160, 160)),
+					color(1.0, (0, 0, 255)),
+					color(1.0, (0, 128, 0)),
+					color(1.0, (128, 64, 0)),
+				],
+				pickup: [color(1.0, (255, 0, 128)), color(1.0, (255, 160, 0))],
+			},
+			// Okabe-Ito colors, health goes blue to yellow to vermillion so it never relies on red and green
+			Theme::ColorblindSafe => Palette {
+				board: color(1.0, (255, 255, 255)),
+				page: color(1.0, (255, 255, 255)),
+				line: color(1.0, (0, 0, 0)),
+				text: RGBColor(0, 0, 0),
+				tank_health: [
+					color(0.5, (196, 196, 196)),
+					color(1.0, (213, 94, 0)),
+					color(1.0, (240, 228, 66)),
+					color(1.0, (0, 114, 178)),
+				],
+				range: [
+					color(0.25, (153, 153, 153)),
+					color(0.3, (86, 180, 233)),
+					color(0.3, (204, 121, 167)),
+				],
+				terrain: [
+					color(1.0, (64, 64, 64)),
+					color(1.0, (153, 204, 238)),
+					color(1.0, (0, 158, 115)),
+					color(1.0, (222, 190, 140)),
+				],
+				pickup: [color(1.0, (204, 121, 167)), color(1.0, (230, 159, 0))],
+			},
+		}
+	}
+}
+
+/// Inclusive rectangle of tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Region {
+	pub x1: u8,
+	pub y1: u8,
+	pub x2: u8,
+	pub y2: u8,
+}
+
+impl Region {
+	/// The corners can be given in any order, anything off the board is clamped to it
+	pub fn new(x1: u8, y1: u8, x2: u8, y2: u8, game: &GameServer) -> Self {
+		Region {
+			x1: x1.min(x2).min(game.width - 1),
+			y1: y1.min(y2).min(game.height - 1),
+			x2: x1.max(x2).min(game.width - 1),
+			y2: y1.max(y2).min(game.height - 1),
+		}
+	}
+
+	pub fn contains(&self, x: u8, y: u8) -> bool {
+		x >= self.x1 && x <= self.x2 && y >= self.y1 && y <= self.y2
+	}
+
+	pub fn whole(game: &GameServer) -> Self {
+		Region::new(0, 0, game.width - 1, game.height - 1, game)
+	}
+
+	/// Square of `radius` tiles around a tile, clamped to the board
+	pub fn around(x: u8, y: u8, radius: u8, game: &GameServer) -> Self {
+		Region::new(
+			x.saturating_sub(radius),
+			y.saturating_sub(radius),
+			x.saturating_add(radius),
+			y.saturating_add(radius),
+			game,
+		)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+	/// Pixels per board tile
+	pub tile_size: u32,
+	/// Shade the area each tank can reach
+	pub show_ranges: bool,
+	/// Write the action count on each tank
+	pub show_actions: bool,
+	/// Number the columns and rows with their zero based `pos_x` and `pos_y`
+	pub show_labels: bool,
+	/// Explain the health and range colors below the board
+	pub show_legend: bool,
+	/// Drawn in place of the plain health circle for any player that has one
+	pub avatars: HashMap<UserId, RgbaImage>,
+	/// Only show this part of the board instead of all of it
+	pub region: Option<Region>,
+	pub theme: Theme,
+}
+
+impl Default for RenderOptions {
+	fn default() -> Self {
+		RenderOptions {
+			tile_size: 25,
+			show_ranges: true,
+			show_actions: true,
+			show_labels: true,
+			show_legend: true,
+			avatars: HashMap::new(),
+			region: None,
+			theme: Theme::default(),
+		}
+	}
+}
+
+/// The last encoded board of each server.  Entries are keyed on a hash of everything that went
+/// into them, so any change to the game is a miss even if nothing invalidated it.
+pub struct RenderCache;
+impl TypeMapKey for RenderCache {
+	type Value = Arc<Mutex<HashMap<GuildId, (u64, Vec<u8>)>>>;
+}
+impl RenderCache {
+	/// Hash of the game, its players, and the options other than the avatars, which `extra` should
+	/// cover along with anything else that changes the output
+	pub fn key(
+		game: &GameServer,
+		players: &[GamePlayer],
+		options: &RenderOptions,
+		extra: impl Hash,
+	) -> u64 {
+		let mut hasher = DefaultHasher::new();
+		game.hash(&mut hasher);
+		players.hash(&mut hasher);
+		options.tile_size.hash(&mut hasher);
+		options.show_ranges.hash(&mut hasher);
+		options.show_actions.hash(&mut hasher);
+		options.show_labels.hash(&mut hasher);
+		options.show_legend.hash(&mut hasher);
+		options.region.hash(&mut hasher);
+		options.theme.hash(&mut hasher);
+		extra.hash(&mut hasher);
+		hasher.finish()
+	}
+
+	async fn entries(ctx: &Context) -> Option<Arc<Mutex<HashMap<GuildId, (u64, Vec<u8>)>>>> {
+		ctx.data.read().await.get::<RenderCache>().cloned()
+	}
+
+	pub async fn get(ctx: &Context, guild_id: GuildId, key: u64) -> Option<Vec<u8>> {
+		let entries = Self::entries(ctx).await?;
+		let entries = entries.lock().unwrap();
+		match entries.get(&guild_id) {
+			Some((cached_key, data)) if *cached_key == key => Some(data.clone()),
+			_ => None,
+		}
+	}
+
+	pub async fn insert(ctx: &Context, guild_id: GuildId, key: u64, data: Vec<u8>) {
+		if let Some(entries) = Self::entries(ctx).await {
+			entries.lock().unwrap().insert(guild_id, (key, data));
+		}
+	}
+
+	pub async fn invalidate(ctx: &Context, guild_id: GuildId) {
+		if let Some(entries) = Self::entries(ctx).await {
+			entries.lock().unwrap().remove(&guild_id);
+		}
+	}
+}
+
+/// Where everything goes, shared by every backend so the bitmap and svg match
+struct Layout {
+	region: Region,
+	tile_size: i32,
+	/// Room for the axis labels on the top and left
+	margin: i32,
+	/// Just the tiles, including the grid line on the far edges
+	board_size: (i32, i32),
+	legend: Vec<LegendEntry>,
+	size: (u32, u32),
+}
+
+fn layout(game: &GameServer, options: &RenderOptions) -> anyhow::Result<Layout> {
+	let region = options.region.unwrap_or_else(|| Region::whole(game));
+	let tile_size = options.tile_size as i32;
+	let board_size = (
+		(region.x2 - region.x1 + 1) as i32 * tile_size + 1,
+		(region.y2 - region.y1 + 1) as i32 * tile_size + 1,
+	);
+	let margin = if options.show_labels { tile_size } else { 0 };
+	let image_width = board_size.0 + margin;
+	let legend = if options.show_legend {
+		let palette = Palette::of(options.theme);
+		layout_legend(
+			image_width,
+			tile_size,
+			&legend_text_style(tile_size, &palette),
+			&palette,
+			game,
+		)?
+	} else {
+		Vec::new()
+	};
+	let legend_height = legend
+		.iter()
+		.map(|entry| entry.pos.1 + tile_size + 4)
+		.max()
+		.unwrap_or(0);
+	Ok(Layout {
+		region,
+		tile_size,
+		margin,
+		board_size,
+		legend,
+		size: (
+			image_width as u32,
+			(board_size.1 + margin + legend_height) as u32,
+		),
+	})
+}
+
+fn legend_text_style(tile_size: i32, palette: &Palette) -> TextStyle<'static> {
+	("sans-serif", tile_size / 2)
+		.into_font()
+		.color(&palette.text)
+		.pos(Pos::new(HPos::Left, VPos::Center))
+}
+
+/// Draws the board with every player on it
+pub fn draw_board(
+	game: &GameServer,
+	players: &[GamePlayer],
+	options: &RenderOptions,
+) -> anyhow::Result<RgbImage> {
+	let layout = layout(game, options)?;
+	let mut image = RgbImage::new(layout.size.0, layout.size.1);
+	{
+		let mut image = BitMapBackend::with_buffer(&mut image, layout.size);
+		draw_board_on(&mut image, game, players, options, &layout)?;
+	}
+	Ok(image)
+}
+
+/// Draws the board as a scalable vector image, showing the same as `draw_board` apart from avatars.
+/// The backend can only draw those a pixel at a time, one `<rect>` each, so tanks keep their plain
+/// health circle instead.
+pub fn render_svg(
+	game: &GameServer,
+	players: &[GamePlayer],
+	options: &RenderOptions,
+) -> anyhow::Result<String> {
+	let without_avatars;
+	let options = if options.avatars.is_empty() {
+		options
+	} else {
+		without_avatars = RenderOptions {
+			avatars: HashMap::new(),
+			..options.clone()
+		};
+		&without_avatars
+	};
+	let layout = layout(game, options)?;
+	let mut data = String::new();
+	{
+		let mut image = SVGBackend::with_string(&mut data, layout.size);
+		draw_board_on(&mut image, game, players, options, &layout)?;
+		image.present()?;
+	}
+	Ok(data)
+}
+
+fn draw_board_on<DB: DrawingBackend>(
+	image: &mut DB,
+	game: &GameServer,
+	players: &[GamePlayer],
+	options: &RenderOptions,
+	layout: &Layout,
+) -> anyhow::Result<()>
+where
+	DB::ErrorType: 'static,
+{
+	let (image_width, image_height) = (layout.size.0 as i32, layout.size.1 as i32);
+	let tile_size = layout.tile_size;
+	let margin = layout.margin;
+	let palette = Palette::of(options.theme);
+	image.draw_rect(
+		(0, 0),
+		(image_width - 1, image_height - 1),
+		&palette.page,
+		true,
+	)?;
+	draw_tiles(
+		image,
+		game,
+		players,
+		options,
+		layout.region,
+		(margin, margin),
+	)?;
+
+	if options.show_labels {
+		let label_style = ("sans-serif", tile_size / 2)
+			.into_text_style(&image.get_size())
+			.color(&palette.text)
+			.pos(Pos::new(HPos::Center, VPos::Center));
+		for (i, x) in (layout.region.x1..=layout.region.x2).enumerate() {
+			let i = i as i32;
+			image.draw_text(
+				&x.to_string(),
+				&label_style,
+				(margin + i * tile_size + tile_size / 2, margin / 2),
+			)?;
+		}
+		for (i, y) in (layout.region.y1..=layout.region.y2).enumerate() {
+			let i = i as i32;
+			image.draw_text(
+				&y.to_string(),
+				&label_style,
+				(margin / 2, margin + i * tile_size + tile_size / 2),
+			)?;
+		}
+	}
+
+	let legend_text_style = legend_text_style(tile_size, &palette);
+	let top = margin + layout.board_size.1 + 4;
+	for entry in layout.legend.iter() {
+		let corner = (entry.pos.0, top + entry.pos.1);
+		let center = (corner.0 + tile_size / 2, corner.1 + tile_size / 2);
+		match entry.swatch {
+			Swatch::Health(health) => {
+				image.draw_circle(
+					center,
+					tile_size as u32 / 3,
+					&palette.tank_health[health as usize],
+					true,
+				)?;
+			}
+			Swatch::Terrain(terrain) => {
+				if let Some(color) = palette.terrain(terrain) {
+					image.draw_rect(
+						corner,
+						(corner.0 + tile_size, corner.1 + tile_size),
+						color,
+						true,
+					)?;
+				}
+				image.draw_rect(
+					corner,
+					(corner.0 + tile_size, corner.1 + tile_size),
+					&palette.line,
+					false,
+				)?;
+			}
+			Swatch::Pickup(kind) => {
+				draw_pickup(image, kind, center, tile_size, &palette)?;
+			}
+			Swatch::Range(range) => {
+				image.draw_rect(
+					corner,
+					(corner.0 + tile_size, corner.1 + tile_size),
+					&palette.board,
+					true,
+				)?;
+				image.draw_rect(
+					corner,
+					(corner.0 + tile_size, corner.1 + tile_size),
+					&palette.range[range as usize - 1],
+					true,
+				)?;
+				image.draw_rect(
+					corner,
+					(corner.0 + tile_size, corner.1 + tile_size),
+					&palette.line,
+					false,
+				)?;
+			}
+		}
+		image.draw_text(
+			&entry.label,
+			&legend_text_style,
+			(corner.0 + tile_size + 4, center.1),
+		)?;
+	}
+	Ok(())
+}
+
+enum Swatch {
+	Health(u8),
+	Range(u8),
+	Terrain(Terrain),
+	Pickup(PickupKind),
+}
+
+struct LegendEntry {
+	label: String,
+	/// Relative to the top left of the legend strip
+	pos: (i32, i32),
+	swatch: Swatch,
+}
+
+/// Flows the legend entries into rows that fit the image width
+fn layout_legend(
+	image_width: i32,
+	tile_size: i32,
+	text_style: &TextStyle,
+	palette: &Palette,
+	game: &GameServer,
+) -> anyhow::Result<Vec<LegendEntry>> {
+	// Only the terrain actually on the board, most games are played on an open one
+	let terrain = Terrain::FEATURES
+		.iter()
+		.copied()
+		.filter(|&terrain| game.terrain.iter().any(|(_, t)| t == terrain))
+		.map(|terrain| {
+			let label = match terrain {
+				Terrain::Forest => "forest, blocks sight".to_string(),
+				Terrain::Hill => "hill, +1 range".to_string(),
+				terrain => terrain.as_str().to_string(),
+			};
+			(label, Swatch::Terrain(terrain))
+		})
+		.collect::<Vec<_>>();
+	let pickups = PickupKind::ALL
+		.iter()
+		.copied()
+		.filter(|&kind| game.pickups.iter().any(|p| p.kind == kind))
+		.map(|kind| {
+			let label = match kind {
+				PickupKind::Heart => "heart, +1 health".to_string(),
+				PickupKind::Crate => format!("crate, +{} actions", CRATE_ACTIONS),
+			};
+			(label, Swatch::Pickup(kind))
+		})
+		.collect::<Vec<_>>();
+	let entries = (0..=MAX_HEALTH)
+		.map(|health| {
+			let label = match health {
+				0 => "destroyed".to_string(),
+				health => format!("{} health", health),
+			};
+			(label, Swatch::Health(health))
+		})
+		.chain((1..=palette.range.len() as u8).map(|range| {
+			let label = if range as usize == palette.range.len() {
+				format!("range {}+", range)
+			} else {
+				format!("range {}", range)
+			};
+			(label, Swatch::Range(range))
+		}))
+		.chain(terrain)
+		.chain(pickups);
+	let mut legend = Vec::new();
+	let (mut x, mut y) = (4, 0);
+	for (label, swatch) in entries {
+		let ((left, _), (right, _)) = text_style
+			.layout_box(&label)
+			.map_err(|e| anyhow::anyhow!("unable to lay out legend text: {:?}", e))?;
+		let width = tile_size + 4 + (right - left) + tile_size / 2;
+		if x > 4 && x + width > image_width {
+			x = 4;
+			y += tile_size + 4;
+		}
+		legend.push(LegendEntry {
+			label,
+			pos: (x, y),
+			swatch,
+		});
+		x += width;
+	}
+	Ok(legend)
+}
+
+/// Just the grid and the tanks on it, with the top left of `region` drawn at `origin`
+fn draw_tiles<DB: DrawingBackend>(
+	image: &mut DB,
+	game: &GameServer,
+	players: &[GamePlayer],
+	options: &RenderOptions,
+	region: Region,
+	origin: (i32, i32),
+) -> anyhow::Result<()>
+where
+	DB::ErrorType: 'static,
+{
+	let tile_size = options.tile_size as i32;
+	let palette = Palette::of(options.theme);
+	// Top left corner of a tile, which can be outside the region
+	let corner = |x: u8, y: u8| {
+		(
+			origin.0 + (x as i32 - region.x1 as i32) * tile_size,
+			origin.1 + (y as i32 - region.y1 as i32) * tile_size,
+		)
+	};
+	// The far edges get one extra pixel for the grid line
+	let far = corner(region.x2 + 1, region.y2 + 1);
+	let clamp = |(x, y): (i32, i32)| (x.max(origin.0).min(far.0), y.max(origin.1).min(far.1));
+	let text_id_size = (tile_size * 2) / 3;
+	let text_id_style = &("sans-serif", text_id_size)
+		.into_text_style(&image.get_size())
+		.color(&palette.text);
+	// Board itself
+	image.draw_rect(origin, far, &palette.board, true)?;
+	for ((x, y), terrain) in game.terrain.iter() {
+		if !region.contains(x, y) {
+			continue;
+		}
+		if let Some(color) = palette.terrain(terrain) {
+			let tile = corner(x, y);
+			image.draw_rect(tile, (tile.0 + tile_size, tile.1 + tile_size), color, true)?;
+		}
+	}
+	for x in region.x1..=region.x2 + 1 {
+		let (x, _) = corner(x, 0);
+		image.draw_line((x, origin.1), (x, far.1), &palette.line)?;
+	}
+	for y in region.y1..=region.y2 + 1 {
+		let (_, y) = corner(0, y);
+		image.draw_line((origin.0, y), (far.0, y), &palette.line)?;
+	}
+
+	// Range indicators, including from tanks outside the region that reach into it
+	let range_of = |p: &GamePlayer| game.terrain.range_at(p.pos_x, p.pos_y, p.range);
+	let max_range = if options.show_ranges {
+		players.iter().map(range_of).max().unwrap_or(1)
+	} else {
+		0
+	};
+	for range in (1..=max_range).rev() {
+		for player in players.iter().filter(|p| range_of(p) == range) {
+			let dist = range as i32 * tile_size + (tile_size / 3);
+			// Range
+			let c = corner(player.pos_x, player.pos_y);
+			let c = (c.0 + (tile_size / 2), c.1 + (tile_size / 2));
+			let (from, to) = (
+				clamp((c.0 - dist, c.1 - dist)),
+				clamp((c.0 + dist, c.1 + dist)),
+			);
+			if from.0 == to.0 || from.1 == to.1 {
+				continue;
+			}
+			image.draw_rect(
+				from,
+				to,
+				// Anything past the last style just reuses it
+				&palette.range[(range as usize).min(palette.range.len()) - 1],
+				true,
+			)?;
+		}
+	}
+
+	for pickup in game
+		.pickups
+		.iter()
+		.filter(|p| region.contains(p.pos_x, p.pos_y))
+	{
+		let tile = corner(pickup.pos_x, pickup.pos_y);
+		let center = (tile.0 + (tile_size / 2), tile.1 + (tile_size / 2));
+		draw_pickup(image, pickup.kind, center, tile_size, &palette)?;
+	}
+
+	for player in players.iter().filter(|p| region.contains(p.pos_x, p.pos_y)) {
+		let tile = corner(player.pos_x, player.pos_y);
+		// Player color
+		for inset in 1..=2 {
+			image.draw_rect(
+				(tile.0 + inset, tile.1 + inset),
+				(tile.0 + tile_size - inset, tile.1 + tile_size - inset),
+				&BackendColor {
+					alpha: 1.0,
+					rgb: player.color.rgb(),
+				},
+				false,
+			)?;
+		}
+		// Health
+		let center = (tile.0 + (tile_size / 2), tile.1 + (tile_size / 2));
+		if player.health > MAX_HEALTH {
+			eprintln!("Invalid player data in game {}: {:?}", game.name, player);
+		} else if let Some(avatar) = options.avatars.get(&player.user_id) {
+			// Ringed in the health color instead
+			let radius = (tile_size * 2) / 5;
+			image.draw_circle(
+				center,
+				radius as u32 + 2,
+				&palette.tank_health[player.health as usize],
+				true,
+			)?;
+			draw_avatar(image, avatar, center, radius, player.health == 0)?;
+		} else {
+			image.draw_circle(
+				center,
+				tile_size as u32 / 3,
+				&palette.tank_health[player.health as usize],
+				true,
+			)?;
+		}
+		// Player ID#
+		let text_offset_x = if player.number < 10 {
+			(3 * text_id_size) / 7
+		} else {
+			0
+		};
+		let center = (tile.0 + 2 + text_offset_x, tile.1 + 2);
+		image.draw_text(&player.number.to_string(), text_id_style, center)?;
+		// Player Actions
+		if !options.show_actions {
+			continue;
+		}
+		let text_offset_x = if player.actions < 10 {
+			(3 * text_id_size) / 7
+		} else {
+			0
+		};
+		let center = (tile.0 + 2 + text_offset_x, tile.1 + (tile_size / 2) + 1);
+		image.draw_text(&player.actions.to_string(), text_id_style, center)?;
+	}
+	Ok(())
+}
+
+/// Draws a pickup as a small heart or crate in the middle of its tile
+fn draw_pickup<DB: DrawingBackend>(
+	image: &mut DB,
+	kind: PickupKind,
+	center: (i32, i32),
+	tile_size: i32,
+	palette: &Palette,
+) -> anyhow::Result<()>
+where
+	DB::ErrorType: 'static,
+{
+	let size = tile_size / 4;
+	let color = palette.pickup(kind);
+	match kind {
+		PickupKind::Heart => {
+			// Two lobes on top of a triangle pointing down
+			let lobe = size / 2;
+			for dx in [-lobe, lobe].iter() {
+				image.draw_circle(
+					(center.0 + dx, center.1 - lobe / 2),
+					lobe as u32 + 1,
+					color,
+					true,
+				)?;
+			}
+			image.fill_polygon(
+				vec![
+					(center.0 - size - 1, center.1 - lobe / 4),
+					(center.0 + size + 1, center.1 - lobe / 4),
+					(center.0, center.1 + size + 1),
+				],
+				color,
+			)?;
+		}
+		PickupKind::Crate => {
+			let (from, to) = (
+				(center.0 - size, center.1 - size),
+				(center.0 + size, center.1 + size),
+			);
+			image.draw_rect(from, to, color, true)?;
+			image.draw_rect(from, to, &palette.line, false)?;
+			image.draw_line(from, to, &palette.line)?;
+			image.draw_line((from.0, to.1), (to.0, from.1), &palette.line)?;
+		}
+	}
+	Ok(())
+}
+
+/// Draws the avatar cropped to a circle, greyed out if the tank is destroyed
+fn draw_avatar<DB: DrawingBackend>(
+	image: &mut DB,
+	avatar: &RgbaImage,
+	center: (i32, i32),
+	radius: i32,
+	destroyed: bool,
+) -> anyhow::Result<()>
+where
+	DB::ErrorType: 'static,
+{
+	let size = radius as u32 * 2;
+	let avatar = image::imageops::resize(avatar, size, size, FilterType::Triangle);
+	for (x, y, pixel) in avatar.enumerate_pixels() {
+		let (dx, dy) = (x as i32 - radius, y as i32 - radius);
+		if dx * dx + dy * dy > radius * radius {
+			continue;
+		}
+		let [r, g, b, a] = pixel.0;
+		let rgb = if destroyed {
+			let l = ((r as u32 * 30 + g as u32 * 59 + b as u32 * 11) / 100) as u8;
+			(l, l, l)
+		} else {
+			(r, g, b)
+		};
+		image.draw_pixel(
+			(center.0 + dx, center.1 + dy),
+			BackendColor {
+				alpha: a as f64 / 255.0,
+				rgb,
+			},
+		)?;
+	}
+	Ok(())
+}
+
+/// Draws the board and encodes it as a PNG
+pub fn render_png(
+	game: &GameServer,
+	players: &[GamePlayer],
+	options: &RenderOptions,
+) -> anyhow::Result<Vec<u8>> {
+	let image = draw_board(game, players, options)?;
+	let (image_width, image_height) = image.dimensions();
+	// Leaving off the 3 or 4 for color as it should compress smaller than that anyway
+	let mut data = Vec::with_capacity(image_width as usize * image_height as usize);
+	{
+		PngEncoder::new(&mut data).encode(
+			image.as_raw().as_slice(),
+			image_width,
+			image_height,
+			ColorType::Rgb8,
+		)?;
+	}
+	Ok(data)
+}
+
+/// Lays the board out as monospace text, one line per row, for anyone who can't use the image
+///
+/// Each tank is shown as `number:health`, with `x` for the health of a destroyed one, a pickup as
+/// its `PickupKind::symbol`, and any other tile as the `Terrain::symbol` of its terrain
+pub fn render_text(
+	game: &GameServer,
+	players: &[GamePlayer],
+	region: Option<Region>,
+) -> Vec<String> {
+	let region = region.unwrap_or_else(|| Region::whole(game));
+	let max_number = players.iter().map(|p| p.number).max().unwrap_or(0);
+	let width = (max_number.to_string().len() + 2).max(region.x2.to_string().len());
+	let label_width = region.y2.to_string().len();
+	let mut lines = Vec::with_capacity((region.y2 - region.y1) as usize + 2);
+	lines.push(
+		std::iter::once(format!("{:>w$}", "", w = label_width))
+			.chain((region.x1..=region.x2).map(|x| format!("{:>w$}", x, w = width)))
+			.collect::<Vec<_>>()
+			.join(" "),
+	);
+	for y in region.y1..=region.y2 {
+		let cells = (region.x1..=region.x2).map(|x| {
+			let cell = match players.iter().find(|p| p.pos_x == x && p.pos_y == y) {
+				Some(p) if p.health == 0 => format!("{}:x", p.number),
+				Some(p) => format!("{}:{}", p.number, p.health),
+				None => match game.pickups.iter().find(|p| p.pos_x == x && p.pos_y == y) {
+					Some(pickup) => pickup.kind.symbol().to_string(),
+					None => game.terrain.get(x, y).symbol().to_string(),
+				},
+			};
+			format!("{:>w$}", cell, w = width)
+		});
+		lines.push(
+			std::iter::once(format!("{:>w$}", y, w = label_width))
+				.chain(cells)
+				.collect::<Vec<_>>()
+				.join(" "),
+		);
+	}
+	lines
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::terrain::TerrainMap;
+	use image::GenericImageView;
+
+	fn game() -> GameServer {
+		let mut terrain = TerrainMap::default();
+		terrain.set(2, 0, Terrain::Wall);
+		terrain.set(3, 1, Terrain::Water);
+		terrain.set(0, 3, Terrain::Forest);
+		terrain.set(4, 3, Terrain::Hill);
+		GameServer {
+			guild_id: GuildId(1),
+			name: "Game".to_string(),
+			width: 5,
+			height: 4,
+			upgrade_cost: 3,
+			max_range: 3,
+			ram_damage: 0,
+			pickup_spawns: 0,
+			status: GameStatus::Running,
+			terrain,
+			pickups: vec![Pickup {
+				pos_x: 1,
+				pos_y: 2,
+				kind: PickupKind::Crate,
+			}],
+		}
+	}
+
+	fn players() -> Vec<GamePlayer> {
+		[(0, 0, 0, 3), (1, 4, 3, 0), (10, 2, 2, 1)]
+			.iter()
+			.map(|&(number, pos_x, pos_y, health)| GamePlayer {
+				guild_id: GuildId(1),
+				user_id: UserId(number as u64 + 1),
+				number,
+				color: PlayerColor::for_number(number),
+				pos_x,
+				pos_y,
+				health,
+				actions: 2,
+				range: 1,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn text_board() {
+		assert_eq!(
+			render_text(&game(), &players(), None),
+			vec![
+				"     0    1    2    3    4",
+				"0  0:3    .    #    .    .",
+				"1    .    .    .    ~    .",
+				"2    .    $ 10:1    .    .",
+				"3    %    .    .    .  1:x",
+			]
+		);
+	}
+
+	#[test]
+	fn text_board_region() {
+		let game = game();
+		assert_eq!(
+			render_text(&game, &players(), Some(Region::new(1, 1, 3, 2, &game))),
+			vec!["     1    2    3", "1    .    .    ~", "2    $ 10:1    ."]
+		);
+	}
+
+	#[test]
+	fn png_board_decodes() {
+		let options = RenderOptions::default();
+		let layout = layout(&game(), &options).unwrap();
+		let data = render_png(&game(), &players(), &options).unwrap();
+		let image = image::load_from_memory(&data).unwrap();
+		assert_eq!(image.width(), layout.size.0);
+		assert_eq!(image.height(), layout.size.1);
+	}
+}
@@ -0,0 +1,147 @@
+use crate::db::*;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, Utc};
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Checks every minute for games whose scheduled supply is due, runs until the process exits
+pub async fn supply_scheduler(http: Arc<Http>, db: SqlitePool) {
+	let mut interval = tokio::time::interval(Duration::from_secs(60));
+	loop {
+		interval.tick().await;
+		if let Err(reason) = run_scheduled_supplies(&http, &db).await {
+			eprintln!("error running scheduled supplies: {:?}", reason);
+		}
+	}
+}
+
+/// First time after `now` that lands on `at` plus a multiple of `interval_hours`
+pub fn next_supply(at: NaiveTime, interval_hours: u16, now: DateTime<Utc>) -> DateTime<Utc> {
+	let interval = ChronoDuration::hours(interval_hours.max(1) as i64);
+	let mut next = DateTime::<Utc>::from_utc(now.date().naive_utc().and_time(at), Utc);
+	while next <= now {
+		next = next + interval;
+	}
+	next
+}
+
+async fn run_scheduled_supplies(http: &Http, db: &SqlitePool) -> anyhow::Result<()> {
+	let now = Utc::now().timestamp();
+	let due = sqlx::query!(
+		r#"
+		SELECT guild_id, name, supply_channel_id AS "supply_channel_id!", supply_interval AS "supply_interval!", supply_actions, supply_next AS "supply_next!"
+		FROM game_servers
+		WHERE supply_next <= ? AND status = 'running' AND supply_channel_id IS NOT NULL AND supply_interval IS NOT NULL
+		"#,
+		now
+	)
+	.fetch_all(db)
+	.await?;
+
+	for game in due {
+		// Each game is supplied in its own transaction, one failing is logged and leaves the rest
+		// to carry on, it is retried on the next tick as its supply is still due
+		let result: anyhow::Result<_> = async {
+			let mut tx = db.begin().await?;
+			let players = tx.get_players(game.guild_id).await?;
+			sqlx::query!(
+				"UPDATE game_server_players SET actions = actions + ? WHERE guild_id = ? AND health > 0",
+				game.supply_actions,
+				game.guild_id
+			)
+			.execute(&mut tx)
+			.await?;
+			for before in players.iter().filter(|p| p.health > 0) {
+				let mut after = before.clone();
+				after.actions = after.actions.saturating_add(game.supply_actions as u8);
+				tx.log_event(
+					game.guild_id,
+					None,
+					Some(before.user_id),
+					EventKind::Supply,
+					Some(before.state()),
+					Some(after.state()),
+				)
+				.await?;
+			}
+			let jury = tx.tally_votes(game.guild_id).await?;
+			let pickups = if game.supply_actions > 0 {
+				tx.spawn_pickups(game.guild_id).await?
+			} else {
+				Vec::new()
+			};
+			// Skip any missed periods, such as when the bot was offline, rather than supplying them all at once
+			let interval = game.supply_interval.max(1);
+			let mut next = game.supply_next + interval;
+			while next <= now {
+				next += interval;
+			}
+			sqlx::query!(
+				"UPDATE game_servers SET supply_next = ? WHERE guild_id = ?",
+				next,
+				game.guild_id
+			)
+			.execute(&mut tx)
+			.await?;
+			tx.commit().await?;
+			Ok((next, jury, pickups))
+		}
+		.await;
+		let (next, jury, pickups) = match result {
+			Ok(result) => result,
+			Err(reason) => {
+				eprintln!(
+					"unable to run scheduled supply in server {}: {:?}",
+					game.guild_id, reason
+				);
+				continue;
+			}
+		};
+		println!(
+			"Scheduled supply of {} actions in server {} game `{}`",
+			game.supply_actions, game.guild_id, game.name
+		);
+
+		let jury = match jury {
+			Some((winner, votes)) => format!(
+				"\nThe jury gave <@{}> an extra action with {} vote{}",
+				winner,
+				votes,
+				if votes == 1 { "" } else { "s" }
+			),
+			None => String::new(),
+		};
+		let pickups = if pickups.is_empty() {
+			String::new()
+		} else {
+			format!(
+				"\n{} pickup{} appeared on the board",
+				pickups.len(),
+				if pickups.len() == 1 { "" } else { "s" }
+			)
+		};
+		if let Err(reason) = ChannelId(game.supply_channel_id as u64)
+			.say(
+				http,
+				format!(
+					"Supplied {} action{} to every living tank in `{}`, next supply <t:{}:R>{}{}",
+					game.supply_actions,
+					if game.supply_actions == 1 { "" } else { "s" },
+					game.name,
+					next,
+					jury,
+					pickups
+				),
+			)
+			.await
+		{
+			eprintln!(
+				"unable to post scheduled supply notice in server {}: {:?}",
+				game.guild_id, reason
+			);
+		}
+	}
+	Ok(())
+}
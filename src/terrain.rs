@@ -0,0 +1,225 @@
+use crate::helpers::{Direction, MapStyle};
+use rand::Rng;
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Terrain {
+	Plain,
+	/// Can't be driven through or seen through
+	Wall,
+	/// Can't be driven through but can be shot over
+	Water,
+	/// Can be driven through but blocks line of sight past it
+	Forest,
+	/// A tank on a hill gets +1 range
+	Hill,
+}
+
+impl FromStr for Terrain {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Ok(match s {
+			"plain" => Terrain::Plain,
+			"wall" => Terrain::Wall,
+			"water" => Terrain::Water,
+			"forest" => Terrain::Forest,
+			"hill" => Terrain::Hill,
+			_ => anyhow::bail!("invalid terrain: {}", s),
+		})
+	}
+}
+
+impl Terrain {
+	/// Everything other than plain, in the order they are generated and listed
+	pub const FEATURES: [Terrain; 4] = [
+		Terrain::Wall,
+		Terrain::Water,
+		Terrain::Forest,
+		Terrain::Hill,
+	];
+
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Terrain::Plain => "plain",
+			Terrain::Wall => "wall",
+			Terrain::Water => "water",
+			Terrain::Forest => "forest",
+			Terrain::Hill => "hill",
+		}
+	}
+
+	/// For messages like "{} is in the way"
+	pub fn describe(self) -> &'static str {
+		match self {
+			Terrain::Plain => "open ground",
+			Terrain::Wall => "a wall",
+			Terrain::Water => "water",
+			Terrain::Forest => "a forest",
+			Terrain::Hill => "a hill",
+		}
+	}
+
+	/// Used by the text board
+	pub fn symbol(self) -> char {
+		match self {
+			Terrain::Plain => '.',
+			Terrain::Wall => '#',
+			Terrain::Water => '~',
+			Terrain::Forest => '%',
+			Terrain::Hill => '^',
+		}
+	}
+
+	pub fn is_passable(self) -> bool {
+		!matches!(self, Terrain::Wall | Terrain::Water)
+	}
+
+	pub fn blocks_sight(self) -> bool {
+		matches!(self, Terrain::Wall | Terrain::Forest)
+	}
+
+	pub fn range_bonus(self) -> u8 {
+		match self {
+			Terrain::Hill => 1,
+			_ => 0,
+		}
+	}
+}
+
+/// Terrain of a whole board, anything not in it is plain
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TerrainMap {
+	tiles: BTreeMap<(u8, u8), Terrain>,
+}
+
+impl TerrainMap {
+	pub fn get(&self, x: u8, y: u8) -> Terrain {
+		self.tiles.get(&(x, y)).copied().unwrap_or(Terrain::Plain)
+	}
+
+	pub fn set(&mut self, x: u8, y: u8, terrain: Terrain) {
+		if terrain == Terrain::Plain {
+			self.tiles.remove(&(x, y));
+		} else {
+			self.tiles.insert((x, y), terrain);
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tiles.is_empty()
+	}
+
+	/// Every tile that isn't plain
+	pub fn iter(&self) -> impl Iterator<Item = ((u8, u8), Terrain)> + '_ {
+		self.tiles.iter().map(|(&pos, &terrain)| (pos, terrain))
+	}
+
+	/// Range of a tank standing on `(x, y)` with the given base range
+	pub fn range_at(&self, x: u8, y: u8, range: u8) -> u8 {
+		range.saturating_add(self.get(x, y).range_bonus())
+	}
+
+	/// Whether nothing between the two tiles blocks sight, the tiles themselves never do
+	pub fn line_of_sight(&self, from: (u8, u8), to: (u8, u8)) -> bool {
+		let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+		let (tx, ty) = (to.0 as i32, to.1 as i32);
+		let (dx, dy) = ((tx - x).abs(), -(ty - y).abs());
+		let (sx, sy) = ((tx - x).signum(), (ty - y).signum());
+		let mut err = dx + dy;
+		loop {
+			let e2 = 2 * err;
+			if e2 >= dy {
+				err += dy;
+				x += sx;
+			}
+			if e2 <= dx {
+				err += dx;
+				y += sy;
+			}
+			if (x, y) == (tx, ty) {
+				return true;
+			}
+			if self.get(x as u8, y as u8).blocks_sight() {
+				return false;
+			}
+		}
+	}
+
+	/// Scatters clumps of terrain over the board, then walls off any pockets that can't be
+	/// driven to from the rest of the board so every tank can reach every other
+	pub fn generate(style: MapStyle, width: u8, height: u8) -> TerrainMap {
+		let mut map = TerrainMap::default();
+		let mut rng = rand::thread_rng();
+		let area = width as f32 * height as f32;
+		for (&terrain, &fraction) in Terrain::FEATURES.iter().zip(style.fractions().iter()) {
+			let target = (area * fraction) as usize;
+			let mut placed = 0;
+			for _attempt in 0..target * 4 {
+				if placed >= target {
+					break;
+				}
+				let (mut x, mut y) = (rng.gen_range(0..width), rng.gen_range(0..height));
+				for _step in 0..rng.gen_range(2..8) {
+					if placed >= target {
+						break;
+					}
+					if map.get(x, y) == Terrain::Plain {
+						map.set(x, y, terrain);
+						placed += 1;
+					}
+					let direction = Direction::ALL[rng.gen_range(0..Direction::ALL.len())];
+					if let Some((nx, ny)) = direction.offset_values(x, y, width, height) {
+						x = nx;
+						y = ny;
+					}
+				}
+			}
+		}
+		map.fill_pockets(width, height);
+		map
+	}
+
+	fn fill_pockets(&mut self, width: u8, height: u8) {
+		let index = |(x, y): (u8, u8)| y as usize * width as usize + x as usize;
+		let mut component = vec![None; width as usize * height as usize];
+		let mut sizes = Vec::new();
+		for y in 0..height {
+			for x in 0..width {
+				if component[index((x, y))].is_some() || !self.get(x, y).is_passable() {
+					continue;
+				}
+				let id = sizes.len();
+				let mut size = 0;
+				let mut queue = VecDeque::new();
+				component[index((x, y))] = Some(id);
+				queue.push_back((x, y));
+				while let Some((x, y)) = queue.pop_front() {
+					size += 1;
+					for &direction in Direction::ALL.iter() {
+						if let Some(next) = direction.offset_values(x, y, width, height) {
+							if component[index(next)].is_none()
+								&& self.get(next.0, next.1).is_passable()
+							{
+								component[index(next)] = Some(id);
+								queue.push_back(next);
+							}
+						}
+					}
+				}
+				sizes.push(size);
+			}
+		}
+		let largest = (0..sizes.len()).max_by_key(|&id| sizes[id]);
+		for y in 0..height {
+			for x in 0..width {
+				if let Some(id) = component[index((x, y))] {
+					if Some(id) != largest {
+						self.set(x, y, Terrain::Wall);
+					}
+				}
+			}
+		}
+	}
+}